@@ -0,0 +1,183 @@
+//! Locations of data files and API credentials.
+//!
+//! Settings come from, in order of precedence:
+//!
+//! 1. environment variables named after the keys, e.g. `CARNISTER_YOUTUBE_API_KEY` for `youtube_api_key`
+//! 2. a TOML file: `--config`, `$CARNISTER_CONFIG`, `./carnister.toml` or
+//!    `$XDG_CONFIG_HOME/carnister/config.toml` (`~/.config/carnister/config.toml`)
+//! 3. defaults below the data directory, which is `./Carnister` if it exists and
+//!    `$XDG_DATA_HOME/carnister` (`~/.local/share/carnister`) otherwise
+//!
+//! ```toml
+//! data_dir = "/srv/carnister"
+//! song_list_dir = "decks"       # relative paths are relative to the config file
+//! output_dir = "print"
+//! design = "designs/retro.svg"
+//! font = "fonts/Inter-Bold.ttf" # the bundled font and icon are used if omitted
+//! icon = "icon.svg"
+//! cache_dir = "/var/cache/carnister"
+//! youtube_api_key = "..."
+//! discogs_token = "..."
+//! spotify_client_id = "..."
+//! spotify_client_secret = "..."
+//! ```
+//!
+//! API credentials not set either way are read from the text files in the data directory.
+
+use std::{error::Error, path::{Path, PathBuf}, sync::OnceLock};
+use log::*;
+use serde::Deserialize;
+
+use crate::{metadata::discogs, sources::{spotify, youtube}};
+
+/// Font of the year on the card fronts, used unless [`Config::font`] is set
+pub const BUNDLED_FONT: &[u8] = include_bytes!("../CalSans-SemiBold.ttf");
+/// Icon on the card backs, used unless [`Config::icon`] is set
+pub const BUNDLED_ICON: &str = include_str!("../icon.svg");
+/// Card background written to the designs directory on first start
+pub const BUNDLED_DESIGN: &str = include_str!("../design0.svg");
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    data_dir: Option<PathBuf>,
+    song_list_dir: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    design: Option<PathBuf>,
+    font: Option<PathBuf>,
+    icon: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    youtube_api_key: Option<String>,
+    discogs_token: Option<String>,
+    spotify_client_id: Option<String>,
+    spotify_client_secret: Option<String>,
+}
+
+pub struct Config {
+    /// Import checkpoint and credential files
+    pub data_dir: PathBuf,
+    pub song_list_dir: PathBuf,
+    pub output_dir: PathBuf,
+    /// Default card background
+    pub design: PathBuf,
+    /// `None` for the bundled font
+    pub font: Option<PathBuf>,
+    /// `None` for the bundled icon
+    pub icon: Option<PathBuf>,
+    /// Metadata lookup results
+    pub cache_dir: PathBuf,
+    pub youtube_api_key: Option<String>,
+    pub discogs_token: Option<String>,
+    /// Client id and secret
+    pub spotify_credentials: Option<(String, String)>,
+}
+
+/// Loads the configuration, `path` overriding the config file search. Call before [`get`].
+pub fn init(path: Option<&Path>) -> Result<&'static Config, Box<dyn Error>> {
+    let config = Config::load(path)?;
+    if CONFIG.set(config).is_err() {
+        return Err("The configuration was already loaded".into());
+    }
+    Ok(get())
+}
+
+/// The configuration loaded by [`init`], or the one found without a `--config` path.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| Config::load(None).unwrap_or_else(|e| {
+        error!("{}, using the default configuration", e);
+        Config::from_file(ConfigFile::default(), None)
+    }))
+}
+
+impl Config {
+
+    fn load(path: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+
+        let path = path.map(Path::to_path_buf)
+            .or_else(|| env_var("CARNISTER_CONFIG").map(PathBuf::from))
+            .or_else(|| Some(PathBuf::from("./carnister.toml")).filter(|p| p.is_file()))
+            .or_else(|| xdg_dir("XDG_CONFIG_HOME", ".config").map(|d| d.join("carnister/config.toml")).filter(|p| p.is_file()));
+
+        let file = match &path {
+            Some(path) => {
+                let content = std::fs::read_to_string(path).map_err(|e| format!("Error reading config file {}: {}", path.display(), e))?;
+                let file: ConfigFile = toml::from_str(&content).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+                info!("Using config file {}", path.display());
+                file
+            },
+            None => ConfigFile::default(),
+        };
+
+        Ok(Config::from_file(file, path.as_deref()))
+    }
+
+    fn from_file(file: ConfigFile, path: Option<&Path>) -> Config {
+
+        // relative paths in the file are relative to the file, those from the environment to the working directory
+        let base = path.and_then(Path::parent).unwrap_or(Path::new("."));
+        let path_setting = |key: &str, value: Option<PathBuf>| env_var(&env_name(key)).map(PathBuf::from).or(value.map(|v| base.join(v)));
+        let setting = |key: &str, value: Option<String>| env_var(&env_name(key)).or(value).filter(|v| !v.trim().is_empty());
+
+        let data_dir = path_setting("data_dir", file.data_dir).unwrap_or_else(default_data_dir);
+
+        let read_key_file = |name: &str| std::fs::read_to_string(data_dir.join(name)).ok().map(|k| k.trim().to_string()).filter(|k| !k.is_empty());
+
+        let spotify_credentials = match (setting("spotify_client_id", file.spotify_client_id), setting("spotify_client_secret", file.spotify_client_secret)) {
+            (Some(id), Some(secret)) => Some((id, secret)),
+            _ => read_key_file(spotify::CREDENTIALS_FILE).and_then(|c| c.split_once(':').map(|(id, secret)| (id.trim().to_string(), secret.trim().to_string()))),
+        };
+
+        Config {
+            song_list_dir: path_setting("song_list_dir", file.song_list_dir).unwrap_or_else(|| data_dir.join("song_lists")),
+            output_dir: path_setting("output_dir", file.output_dir).unwrap_or_else(|| data_dir.join("output")),
+            design: path_setting("design", file.design).unwrap_or_else(|| data_dir.join("designs/design0.svg")),
+            font: path_setting("font", file.font),
+            icon: path_setting("icon", file.icon),
+            cache_dir: path_setting("cache_dir", file.cache_dir).unwrap_or_else(|| data_dir.join("cache")),
+            youtube_api_key: setting("youtube_api_key", file.youtube_api_key).or_else(|| read_key_file(youtube::API_KEY_FILE)),
+            discogs_token: setting("discogs_token", file.discogs_token).or_else(|| read_key_file(discogs::TOKEN_FILE)),
+            spotify_credentials,
+            data_dir,
+        }
+    }
+
+    pub fn font_data(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        match &self.font {
+            Some(path) => std::fs::read(path).map_err(|e| format!("Error reading font {}: {}", path.display(), e).into()),
+            None => Ok(BUNDLED_FONT.to_vec()),
+        }
+    }
+
+    pub fn icon_svg(&self) -> Result<String, Box<dyn Error>> {
+        match &self.icon {
+            Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Error reading icon {}: {}", path.display(), e).into()),
+            None => Ok(BUNDLED_ICON.to_string()),
+        }
+    }
+}
+
+/// `CARNISTER_YOUTUBE_API_KEY` for `youtube_api_key`
+fn env_name(key: &str) -> String {
+    format!("CARNISTER_{}", key.to_uppercase())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// `./Carnister` where it exists, so existing setups keep working
+fn default_data_dir() -> PathBuf {
+    let local = PathBuf::from("./Carnister");
+    if local.is_dir() {
+        return local;
+    }
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|d| d.join("carnister")).unwrap_or(local)
+}
+
+/// `$variable`, or `$HOME/fallback` if it isn't set
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    env_var(variable).map(PathBuf::from).filter(|p| p.is_absolute())
+        .or_else(|| env_var("HOME").map(|home| Path::new(&home).join(fallback)))
+}
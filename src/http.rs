@@ -0,0 +1,246 @@
+//! HTTP requests with per-host rate limiting and retries, mostly for JSON APIs.
+//!
+//! Requests to the same host are spaced out according to [`RATE_LIMITS`]. Overloaded
+//! responses (429, 503 and YouTube's transient rate limit errors) are retried with
+//! exponential backoff, honoring `Retry-After` when the server sends one. An exhausted
+//! YouTube quota is reported as an error right away since retrying cannot help.
+
+use std::{collections::HashMap, fmt, sync::{LazyLock, Mutex}, time::Duration};
+use log::*;
+use reqwest::{header::{HeaderMap, HeaderValue, RETRY_AFTER, USER_AGENT}, Client, Request, Response, StatusCode};
+use serde_json::Value;
+use tokio::time::Instant;
+
+/// Minimum time between two requests to a host. MusicBrainz accepts around 1 request per second,
+/// Discogs 60 per minute.
+pub const RATE_LIMITS: [(&str, Duration); 2] = [
+    ("musicbrainz.org", Duration::from_millis(1050)),
+    ("api.discogs.com", Duration::from_millis(1050)),
+];
+
+const MAX_RETRIES: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Earliest time the next request to a host may be sent
+static NEXT_REQUEST: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// An error status that retrying can't fix, e.g. 404 for something that doesn't exist.
+#[derive(Debug)]
+pub struct StatusError {
+    pub host: String,
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} request failed with status {}: {}", self.host, self.status, self.body)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+pub async fn receive_json(client: &Client, url: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    request_json(client, url, None).await
+}
+
+/// [`receive_json`] for APIs that need an OAuth access token.
+pub async fn receive_json_authorized(client: &Client, url: &str, bearer_token: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    request_json(client, url, Some(bearer_token)).await
+}
+
+/// [`receive_json`] with extra `headers`, e.g. for APIs that take their key in a header.
+pub async fn receive_json_with_headers(client: &Client, url: &str, headers: HeaderMap) -> Result<Value, Box<dyn std::error::Error>> {
+    let request = client.get(url).headers(headers).build().map_err(|_| format!("Non valid url: {}", url))?;
+    parse_json(send(client, request).await?).await
+}
+
+/// The body of a web page. `headers` replace the default ones, e.g. the user agent.
+pub async fn receive_text(client: &Client, url: &str, headers: HeaderMap) -> Result<String, Box<dyn std::error::Error>> {
+    let request = client.get(url).headers(headers).build().map_err(|_| format!("Non valid url: {}", url))?;
+    Ok(send(client, request).await?.text().await?)
+}
+
+/// Posts `body` as JSON and returns the answer. `headers` replace the default ones.
+pub async fn post_json(client: &Client, url: &str, headers: HeaderMap, body: &Value) -> Result<Value, Box<dyn std::error::Error>> {
+    let request = client.post(url).headers(headers).json(body).build().map_err(|_| format!("Non valid url: {}", url))?;
+    parse_json(send(client, request).await?).await
+}
+
+/// Posts `form` URL-encoded and returns the JSON answer, e.g. for OAuth token requests.
+pub async fn post_form(client: &Client, url: &str, basic_auth: Option<(&str, &str)>, form: &[(&str, &str)]) -> Result<Value, Box<dyn std::error::Error>> {
+
+    let mut request = client.post(url).form(form);
+    if let Some((user, password)) = basic_auth {
+        request = request.basic_auth(user, Some(password));
+    }
+    let request = request.build().map_err(|_| format!("Non valid url: {}", url))?;
+
+    parse_json(send(client, request).await?).await
+}
+
+async fn request_json(client: &Client, url: &str, bearer_token: Option<&str>) -> Result<Value, Box<dyn std::error::Error>> {
+
+    let mut request = client.get(url);
+    if let Some(token) = bearer_token {
+        request = request.bearer_auth(token);
+    }
+    let request = request.build().map_err(|_| format!("Non valid url: {}", url))?;
+
+    parse_json(send(client, request).await?).await
+}
+
+async fn parse_json(response: Response) -> Result<Value, Box<dyn std::error::Error>> {
+    let host = response.url().host_str().unwrap_or("json").to_string();
+    let json: Value = response.json().await?;
+    if let Some(error) = json.get("error") {
+        return Err(format!("{} returned an error: {}", host, error).into());
+    }
+    Ok(json)
+}
+
+/// Sends the request once its host is free, retrying overloaded responses. Returns successful responses.
+async fn send(client: &Client, mut request: Request) -> Result<Response, Box<dyn std::error::Error>> {
+
+    let host = request.url().host_str().unwrap_or("json").to_string();
+
+    request.headers_mut().entry(USER_AGENT).or_insert(HeaderValue::from_static("Carnister/1.0 (https://github.com/Asecave/Carnister/issues)"));
+
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        wait_for_rate_limit(&host).await;
+
+        let attempt_request = request.try_clone().ok_or("Streamed requests can't be retried")?;
+
+        let response = match client.execute(attempt_request).await {
+            Ok(response) => response,
+            Err(e) if (e.is_timeout() || e.is_connect()) && attempt <= MAX_RETRIES => {
+                warn!("{} request failed ({}), retrying in {}s", host, e, backoff.as_secs());
+                defer_host(&host, backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let retry_after = parse_retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+
+        let retryable = match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+            StatusCode::FORBIDDEN => match youtube_error_reason(&body).as_deref() {
+                Some("quotaExceeded" | "dailyLimitExceeded") => {
+                    return Err(format!("{} quota exhausted. The quota resets daily, try again later.", host).into());
+                },
+                Some("rateLimitExceeded" | "userRateLimitExceeded") => true,
+                _ => false,
+            },
+            _ => false,
+        };
+
+        if !retryable {
+            return Err(Box::new(StatusError { host, status, body }));
+        }
+        if attempt > MAX_RETRIES {
+            return Err(format!("{} request failed with status {}, gave up after {} retries", host, status, MAX_RETRIES).into());
+        }
+
+        let wait = retry_after.unwrap_or(backoff);
+        warn!("{} answered {}, retrying in {}s", host, status, wait.as_secs());
+        defer_host(&host, wait);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn wait_for_rate_limit(host: &str) {
+
+    let interval = RATE_LIMITS.iter().find(|(h, _)| host == *h || host.ends_with(&format!(".{}", h))).map(|(_, d)| *d).unwrap_or_default();
+
+    let send_at = {
+        let mut next_request = NEXT_REQUEST.lock().unwrap();
+        let now = Instant::now();
+        let send_at = next_request.get(host).copied().unwrap_or(now).max(now);
+        next_request.insert(host.to_string(), send_at + interval);
+        send_at
+    };
+
+    tokio::time::sleep_until(send_at).await;
+}
+
+/// Holds back all further requests to `host` for at least `wait`.
+fn defer_host(host: &str, wait: Duration) {
+    let mut next_request = NEXT_REQUEST.lock().unwrap();
+    let until = Instant::now() + wait;
+    let entry = next_request.entry(host.to_string()).or_insert(until);
+    *entry = (*entry).max(until);
+}
+
+/// Only the delay-seconds form is supported, HTTP dates are ignored.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers.get(RETRY_AFTER)?.to_str().ok()?.trim().parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_BACKOFF * 5))
+}
+
+/// `reason` of the first entry in a Google API error body
+fn youtube_error_reason(body: &str) -> Option<String> {
+    let json: Value = serde_json::from_str(body).ok()?;
+    json["error"]["errors"][0]["reason"].as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use tiny_http::{Header, Response, Server};
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_error_statuses_by_type() {
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/isrc/GBUM71029604", server.server_addr().to_ip().unwrap());
+        thread::spawn(move || {
+            let request = server.recv().unwrap();
+            request.respond(Response::from_string("{}").with_status_code(404)).unwrap();
+        });
+
+        let error = receive_json(&Client::new(), &url).await.unwrap_err();
+        let status = error.downcast_ref::<StatusError>().map(|e| e.status);
+        assert_eq!(status, Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn retries_posts_when_overloaded() {
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/browse", server.server_addr().to_ip().unwrap());
+        thread::spawn(move || {
+            let mut request = server.recv().unwrap();
+            let retry_after = Header::from_bytes(&b"Retry-After"[..], &b"0"[..]).unwrap();
+            request.respond(Response::from_string("").with_status_code(429).with_header(retry_after)).unwrap();
+
+            request = server.recv().unwrap();
+            let user_agent = request.headers().iter().find(|h| h.field.equiv("User-Agent")).map(|h| h.value.to_string());
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let answer = serde_json::json!({ "user_agent": user_agent, "body": body });
+            request.respond(Response::from_string(answer.to_string())).unwrap();
+        });
+
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("Browser"));
+        let json = post_json(&Client::new(), &url, headers, &serde_json::json!({ "continuation": "abc" })).await.unwrap();
+        assert_eq!(json["user_agent"], "Browser");
+        assert_eq!(json["body"], r#"{"continuation":"abc"}"#);
+    }
+}
@@ -0,0 +1,174 @@
+use core::fmt;
+use std::{error::Error, path::PathBuf};
+use clap::ValueEnum;
+use colored::Colorize;
+use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
+use log::*;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, metadata::{confidence::match_confidence, LookupResult, MetadataLookup}, review::resolve_skipped, sources::{SongSource, SourceKind, SourceTrack}, Song};
+
+/// How songs without a confident MusicBrainz match get their release year during an import.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum YearPolicy {
+    /// Prompt for every song
+    Ask,
+    /// Use the year the source states, e.g. the YouTube upload year
+    #[value(alias = "source")]
+    Youtube,
+    /// Leave the song out of the list
+    Drop,
+}
+
+/// Settings for [`import_playlist`] and [`resume_import`].
+#[derive(Clone, Copy)]
+pub struct ImportOptions {
+    pub year_policy: YearPolicy,
+    /// Songs whose best MusicBrainz result has a lower confidence are handled like songs that were not found
+    pub min_confidence: f32,
+}
+
+/// Progress of a playlist import, written to disk after every song so an interrupted import can be resumed.
+#[derive(Serialize, Deserialize)]
+pub struct ImportCheckpoint {
+    #[serde(default)]
+    pub source: SourceKind,
+    pub playlist_id: String,
+    pub tracks: Vec<SourceTrack>,
+    /// Number of tracks already looked up
+    pub processed: usize,
+    pub songs: Vec<Song>,
+    /// Songs waiting for manual intervention
+    pub skipped: Vec<Song>,
+}
+
+impl ImportCheckpoint {
+
+    pub fn path() -> PathBuf {
+        config::get().data_dir.join("import_checkpoint.json")
+    }
+
+    /// The checkpoint of an interrupted import, if there is one.
+    pub fn load() -> Option<ImportCheckpoint> {
+        let path = Self::path();
+        let content = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => {
+                warn!("Ignoring unreadable import checkpoint {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        // write to a temporary file first so a crash mid-write keeps the previous checkpoint
+        let path = Self::path();
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Deletes the checkpoint once the imported songs are safely stored in a song list.
+    pub fn remove() -> Result<(), Box<dyn Error>> {
+        match std::fs::remove_file(Self::path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub async fn import_playlist(client: &Client, metadata: &MetadataLookup, multi: &MultiProgress, source: &dyn SongSource, playlist_id: &str, options: ImportOptions) -> Result<Vec<Song>, Box<dyn Error>> {
+
+    let tracks = source.fetch_tracks(client, playlist_id).await?;
+
+    let checkpoint = ImportCheckpoint { source: source.kind(), playlist_id: playlist_id.to_string(), tracks, processed: 0, songs: Vec::new(), skipped: Vec::new() };
+    checkpoint.save()?;
+
+    resume_import(client, metadata, multi, checkpoint, options).await
+}
+
+/// Continues looking up the remaining tracks of the checkpoint.
+pub async fn resume_import(client: &Client, metadata: &MetadataLookup, multi: &MultiProgress, mut checkpoint: ImportCheckpoint, options: ImportOptions) -> Result<Vec<Song>, Box<dyn Error>> {
+
+    info!("Receiving data...");
+
+    let pb = multi.add(ProgressBar::new(checkpoint.tracks.len() as u64));
+    pb.set_style(ProgressStyle::with_template("[{elapsed_precise}] [{wide_bar:.cyan/black}] {pos:>7}/{len:7} ({eta})")
+    .unwrap()
+    .with_key("eta", |state: &ProgressState, w: &mut dyn fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
+        .progress_chars("=>-"));
+
+    while checkpoint.processed < checkpoint.tracks.len() {
+
+        pb.set_position(checkpoint.processed as u64);
+
+        let track = checkpoint.tracks[checkpoint.processed].clone();
+        let (artist, title) = (track.artist, track.title);
+        let base_song = Song{youtube_year: track.year, video_id: track.id, source: checkpoint.source, raw_title: track.raw_title, isrc: track.isrc.clone(), ..Default::default()};
+
+        if let Some(release_year) = track.release_year {
+            // e.g. an original year tag, which needs no lookup
+            checkpoint.songs.push(Song{artist, title, release_year, year_justification: track.year_justification, ..base_song});
+        } else {
+            match metadata.lookup(client, &artist, &title, track.isrc.as_deref()).await {
+                Ok(results) => {
+                    // the lookup puts its preferred result first, so keep that order among confident matches
+                    let scored: Vec<(f32, &LookupResult)> = results.iter().map(|result| (match_confidence(&artist, &title, track.duration, result), result)).collect();
+                    let (confidence, result) = scored.iter().find(|(confidence, _)| *confidence >= options.min_confidence)
+                        .or_else(|| scored.iter().reduce(|best, next| if next.0 > best.0 { next } else { best }))
+                        .copied()
+                        .unwrap();
+                    let song = Song{artist, title, release_year: result.year, detected_title: Some(result.detected_title.clone()), year_justification: Some(result.justification.clone()), match_confidence: Some(confidence), ..base_song};
+                    if confidence >= options.min_confidence {
+                        checkpoint.songs.push(song);
+                    } else {
+                        warn!("{} {} - {} ({:.0}%), {}", "Uncertain match:".yellow(), song.artist.yellow(), song.title.yellow(), confidence * 100.0, "Skipping for now.".yellow());
+                        checkpoint.skipped.push(song);
+                    }
+                },
+                Err(_) => {
+                    warn!("{} {} - {}, {}", "Song not found.".red(), artist.red(), title.red(), "Skipping for now.".red());
+                    checkpoint.skipped.push(Song{artist, title, release_year: base_song.youtube_year, ..base_song});
+                }
+            }
+        }
+
+        checkpoint.processed += 1;
+        checkpoint.save()?;
+    }
+
+    pb.finish_with_message("All data received.");
+    multi.remove(&pb);
+
+    match options.year_policy {
+        YearPolicy::Ask => {
+            // every resolved song is checkpointed, so an interrupted review only asks for the rest
+            let skipped = checkpoint.skipped.clone();
+            resolve_skipped(client, metadata, skipped, |song| {
+                checkpoint.skipped.remove(0);
+                checkpoint.songs.push(song);
+                checkpoint.save()
+            }).await?;
+        },
+        YearPolicy::Youtube => {
+            info!("Using the {} for {} songs that were not found or matched uncertainly.", checkpoint.source.year_description(), checkpoint.skipped.len());
+            for song in checkpoint.skipped.iter_mut() {
+                song.release_year = song.youtube_year;
+                song.year_justification = Some(song.source.year_description().into());
+            }
+        },
+        YearPolicy::Drop => {
+            info!("Dropping {} songs that were not found or matched uncertainly.", checkpoint.skipped.len());
+            checkpoint.skipped.clear();
+        },
+    }
+
+    let mut skipped = std::mem::take(&mut checkpoint.skipped);
+    checkpoint.songs.append(&mut skipped);
+    checkpoint.save()?;
+
+    Ok(checkpoint.songs)
+}
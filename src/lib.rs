@@ -0,0 +1,42 @@
+//! Library behind the Carnister binary: fetching playlists, resolving release years,
+//! reviewing song lists and rendering printable cards.
+
+pub mod config;
+pub mod http;
+pub mod import;
+pub mod metadata;
+pub mod render;
+pub mod review;
+pub mod serve;
+pub mod song;
+pub mod song_list;
+pub mod sources;
+
+pub use song::Song;
+
+use std::{future::Future, pin::Pin};
+use tokio::fs;
+
+/// Future returned by the provider and source traits, which need to be object safe.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// Creates the configured directories, empty credential files to fill in and the default design.
+pub async fn create_folder_structure_idempotent() -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::get();
+    fs::create_dir_all(&config.data_dir).await?;
+    fs::create_dir_all(&config.song_list_dir).await?;
+    fs::create_dir_all(&config.output_dir).await?;
+    for file in [sources::youtube::API_KEY_FILE, metadata::discogs::TOKEN_FILE] {
+        let path = config.data_dir.join(file);
+        if !fs::try_exists(&path).await? {
+            fs::write(&path, "").await?;
+        }
+    }
+    if !fs::try_exists(&config.design).await? {
+        if let Some(dir) = config.design.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        fs::write(&config.design, config::BUNDLED_DESIGN).await?;
+    }
+    Ok(())
+}
@@ -1,901 +1,522 @@
-
-use core::fmt;
-use std::{cmp::min, error::Error, fs::File, io::{BufRead, BufReader}, process::exit, time::Duration};
+use std::{error::Error, io::{IsTerminal, Write}, net::{Ipv4Addr, SocketAddr}, path::{Path, PathBuf}, process::exit, time::Duration};
+use carnister::{config, create_folder_structure_idempotent, import::{import_playlist, resume_import, ImportCheckpoint, ImportOptions, YearPolicy}, metadata::{confidence::DEFAULT_MIN_CONFIDENCE, MetadataLookup, ProviderKind, Strategy}, review::{duplicates::{find_duplicates, resolve_duplicates}, input_num, print_input_arrow, review_songs, tui::review_songs_tui, web::{review_songs_web, DEFAULT_REVIEW_PORT}}, serve::{default_base_url, serve_folder, DEFAULT_PORT}, render::{layout::{PageLayout, PaperSize}, render_calibration, render_cards, OutputFormat, RenderOptions}, song_list::{csv::write_csv_song_list, edit_log::EditLog, list_song_lists, load_song_list, load_song_list_with_edits, migrate_legacy_song_lists, save_song_list_with_edits}, sources::{local::LocalFolder, playlist_file::PlaylistFile, spotify::{self, Spotify}, youtube::YouTube, SongSource}, Song};
+use clap::{Args, Parser, Subcommand};
 use colored::Colorize;
 use env_logger::{Builder, Env};
-use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
+use indicatif::MultiProgress;
 use indicatif_log_bridge::LogWrapper;
 use log::*;
-use qrcode_generator::QrCodeEcc;
-use regex::Regex;
-use reqwest::{header::{HeaderValue, USER_AGENT}, Client, Url};
-use rusttype::{Font, Point};
-use serde_json::Value;
+use reqwest::Client;
 use text_io::read;
-use text_svg::Text;
-use tokio::fs;
-use std::io::Write;
-
-struct Song {
-    artist: String,
-    title: String,
-    release_year: i32,
-    youtube_year: i32,
-    video_id: String,
-    raw_title: String,
-    detected_title: Option<String>,
-}
 
-impl fmt::Display for Song {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{:?}", self.artist, self.title, self.release_year, self.youtube_year, self.video_id, self.raw_title, self.detected_title, sep=char::from(31))
-    }
-}
+#[derive(Parser)]
+#[command(name = "carnister", version, about = "Generates printable music quiz cards from playlists")]
+struct Cli {
+    /// Config file [default: $CARNISTER_CONFIG, ./carnister.toml or ~/.config/carnister/config.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+    /// Directory the rendered card pages are written to [default: configured output_dir]
+    #[arg(long, global = true)]
+    output_dir: Option<PathBuf>,
 
-    let logger = 
-        Builder::from_env(Env::default().default_filter_or("info"))
-        .format(|buf, record| {
+    /// Background design used for the card fronts [default: configured design]
+    #[arg(long, global = true)]
+    design: Option<PathBuf>,
 
-            let level = match record.level() {
-                log::Level::Info  => "INFO ".green(),
-                log::Level::Warn  => "WARN ".yellow(),
-                log::Level::Error => "ERROR".red(),
-                log::Level::Debug => "DEBUG".blue(),
-                log::Level::Trace => "TRACE".blue(),
-            };
+    /// File format of the rendered cards
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Pdf)]
+    format: OutputFormat,
 
-            let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-            writeln!(buf, "{}{} {}{} {}", "[".truecolor(150, 150, 150), timestamp.truecolor(150, 150, 150), level, "]".truecolor(150, 150, 150), record.args())
-        })
-        .build();
-    let level = logger.filter();
-    let multi = MultiProgress::new();
+    #[command(flatten)]
+    layout: LayoutArgs,
 
-    LogWrapper::new(multi.clone(), logger)
-        .try_init()
-        .unwrap();
-    log::set_max_level(level);
+    /// Sources of release years, in order of preference
+    #[arg(long, global = true, value_enum, value_delimiter = ',', default_value = "musicbrainz")]
+    providers: Vec<ProviderKind>,
 
-    if let Err(e) = create_folder_structure_idempotent().await {
-        println!("Creating folder structure was unsuccessful");
-        return Err(e);
-    }
+    /// How the answers of several providers are combined
+    #[arg(long, global = true, value_enum, default_value_t = Strategy::Chain)]
+    strategy: Strategy,
 
-    println!();
-    println!("Actions:");
-    println!();
-    println!("{}{}", "1 ".blue(), "Load song list from YouTube playlist".cyan());
-    println!("{}{}", "2 ".blue(), "Load song list from file".cyan());
-    println!();
-    println!("Enter number:");
-    let input = input_num(1, 2);
-    
-    let mut songs: Vec<Song> = Vec::new();
-    let client = Client::new();
-    
-    loop {
-        if input == 1 {
+    /// Ignore cached lookup results and query again
+    #[arg(long, global = true)]
+    refresh: bool,
 
-            let api_key = std::fs::read("./Carnister/youtube_api_key.txt").expect("Error reading youtube api key file").iter().fold(String::new(), |a, b| a + &(*b as char).to_string());
-            //let playlist_id = "PLP9X6Hp3ZLpOsDk3AudxA5FueNmcrQTLr";
-            //let playlist_id = "PLTUl2dTYKo6qyyf0CC5d9yQdt_oMkm-4b";
+    /// Days after which cached lookup results are queried again
+    #[arg(long, global = true, default_value_t = 30)]
+    cache_ttl_days: u64,
 
-            println!("Enter playlist link or id:");
-            print_input_arrow();
-            let input: String = read!("{}\n");
-            let playlist_id = match input.starts_with("http") {
-                true => match input.rsplit_once("list=") {
-                    Some((_, id)) => match id.split_once("&") {
-                        Some((id, _)) => id,
-                        None => id
-                    },
-                    None => {
-                        error!("Invalid playlist link");
-                        continue;
-                    }
+    /// Review with line-based prompts instead of the full-screen review
+    #[arg(long, global = true)]
+    plain: bool,
 
-                },
-                false => input.as_str()
-            };
-            
-            if api_key.is_empty() {
-                error!("No YouTube API key specified. Put your YouTube API key in the Carnister/youtube_api_key.txt file.");
-                exit(1);
-            }
+    /// Runs the interactive menu if omitted
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
+#[derive(Args)]
+#[command(next_help_heading = "Page layout (lengths in mm)")]
+struct LayoutArgs {
+    /// Paper size preset
+    #[arg(long, global = true, value_enum, default_value_t = PaperSize::A4)]
+    paper: PaperSize,
 
-            info!("Fetching videos from playlist...");
-
-            let videos = fetch_videos(&api_key, playlist_id).await.expect("Error while fetching videos");
-
-            let mut skipped: Vec<Song> = Vec::new();
-            let timeout = 1050;
-
-            info!("Setting request delay to {}ms to not get rate limited (MusicBrainz accepts around 1 request per second)", timeout);
-            info!("Receiving data...");
-
-            let pb = multi.add(ProgressBar::new(videos.len() as u64));
-            pb.set_style(ProgressStyle::with_template("[{elapsed_precise}] [{wide_bar:.cyan/black}] {pos:>7}/{len:7} ({eta})")
-            .unwrap()
-            .with_key("eta", |state: &ProgressState, w: &mut dyn fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-                .progress_chars("=>-"));
-
-            for (progress_bar_pos, video) in videos.into_iter().enumerate() {
-                
-                pb.set_position(progress_bar_pos as u64);
-                
-                let id = video["contentDetails"]["videoId"].to_string().trim_matches('\"').to_string();
-                let raw_title = video["snippet"]["title"].to_string().trim_matches('\"').to_string();
-                let upload_channel = video["snippet"]["videoOwnerChannelTitle"].to_string().trim_matches('\"').to_string();
-                let raw_upload_date = video["contentDetails"]["videoPublishedAt"].to_string().trim_matches('\"').to_string();
-
-                let mut tmp_upload_date = raw_upload_date.clone();
-                tmp_upload_date.truncate(raw_upload_date.find("-").unwrap());
-                
-                let title;
-                let artist;
-                let upload_date = tmp_upload_date.parse::<i32>().unwrap();
-
-                if !raw_title.contains(" - ") {
-                    artist = clean_artist(&upload_channel.replace(" - Topic", ""));
-                    title = clean_title(&raw_title);
-                } else {
-                    let split_title: Vec<&str> = raw_title.split(" - ").collect();
-                    artist = clean_artist(split_title[0]);
-                    title = clean_title(split_title[1]);
-                }
+    /// Overrides the paper width of the preset
+    #[arg(long, global = true)]
+    paper_width: Option<f32>,
 
-                tokio::time::sleep(Duration::from_millis(timeout)).await;
+    /// Overrides the paper height of the preset
+    #[arg(long, global = true)]
+    paper_height: Option<f32>,
 
-                let (year, detected_title, _) = match get_music_braiz_results(&client, &artist, &title).await {
-                    Ok(results) => results[0].clone(),
-                    Err(_) => {
-                        warn!("{} {} - {}, {}", "Song not found.".red(), artist.red(), title.red(), "Skipping for now.".red());
-                        skipped.push(Song{artist, title, release_year: upload_date, youtube_year: upload_date, video_id: id, raw_title, detected_title: None});
-                        continue;
-                    }
-                };
+    /// Minimum distance between the paper edge and the cards
+    #[arg(long, global = true, default_value_t = 0.0)]
+    margin: f32,
 
-                let song = Song{artist, title, release_year: year, youtube_year: upload_date, video_id: id, raw_title, detected_title: Some(detected_title)};
+    /// Width and height of a card
+    #[arg(long, global = true, default_value_t = 65.0)]
+    card_size: f32,
 
-                songs.push(song);
-            }
+    /// Space between neighbouring cards
+    #[arg(long, global = true, default_value_t = 0.0)]
+    gutter: f32,
 
-            pb.finish_with_message("All data received.");
-            multi.remove(&pb);
+    /// Extend each card's background past its trim edge by this much
+    #[arg(long, global = true, default_value_t = 0.0)]
+    bleed: f32,
 
-            println!();
-            println!();
-            info!("Revisiting songs that need manual intervention.");
-
-            let mut action_for_all = -1;
-
-            let mut current = 0;
-            let total_skipped = skipped.len() + 1;
-
-            for song in skipped.iter_mut() {
-                loop {
-                    if action_for_all == -1 {
-                        println!();
-                        current += 1;
-                        println!("{}{}{}", current.to_string().green(), "/".green(), total_skipped.to_string().green());
-                        println!();
-                        println!("Youtube title:  {}", song.raw_title.bright_green());
-                        println!("Queried title:  {} - {}", song.artist.bright_green(), song.title.bright_green());
-                        println!();
-                        println!("Actions:");
-                        println!("{} {}{}{}", "1".blue(), "Use YouTube upload date (".cyan(), song.youtube_year.to_string().blue(), ")".cyan());
-                        println!("{} {}", "2".blue(), "Manually set release year".cyan());
-                        println!("{} {}", "3".blue(), "Edit song name for database query".cyan());
-                        println!("{} {}", "4".blue(), "Use YouTube upload date for all remaining".cyan());
-                        println!("{} {}", "5".blue(), "Manually set release year for all remaining".cyan());
-                        println!();
-                        println!("Enter number:");
-                    }
-                    let mut input = 0;
-                    if action_for_all == -1 {
-                        input = input_num(1, 5);
-                        if input == 4 {action_for_all = 1}
-                        if input == 5 {action_for_all = 2}
-                    }
-                    if action_for_all != -1 {
-                        input = action_for_all;
-                    }
+    /// Draw crop marks in the page margin
+    #[arg(long, global = true)]
+    crop_marks: bool,
 
-                    match input {
-                        1 => (),
-                        2 => {
-                            println!("Enter year for {}:", song.raw_title.bright_green());
-                            song.release_year = input_num(i32::MIN, i32::MAX);
-                        },
-                        3 => {
-                            match custom_query(&client, song).await {
-                                Ok(_) => (),
-                                Err(_) => continue,
-                            }
-                        },
-                        _ => return Err("unknown input".into()),
-                    }
-                    info!("Using {} for {}", song.release_year.to_string().green(), song.raw_title.cyan());
-                    break;
-                }
-            }
-
-            info!("All dates specified. Continuing with final rewiew...");
+    /// Draw a thin line along every card's trim edge
+    #[arg(long, global = true)]
+    cut_lines: bool,
 
-            songs.append(&mut skipped);
+    /// Shift the back pages to the right (negative: left) to fix duplex misregistration
+    #[arg(long, global = true, default_value_t = 0.0, allow_negative_numbers = true)]
+    back_offset_x: f32,
 
-        } else {
-            let dir = std::fs::read_dir("./Carnister/song_lists").expect("Could not read song_lists dir");
+    /// Shift the back pages down (negative: up)
+    #[arg(long, global = true, default_value_t = 0.0, allow_negative_numbers = true)]
+    back_offset_y: f32,
 
-            let mut files = Vec::new();
+    /// Rotate the back pages clockwise around the page center, in degrees
+    #[arg(long, global = true, default_value_t = 0.0, allow_negative_numbers = true)]
+    back_rotation: f32,
+}
 
-            println!("Select a file:");
-            let mut count = 1;
-            for path in dir {
-                match path {
-                    Ok(path) => {
-                        println!("{} {}", count.to_string().blue(), path.file_name().into_string().unwrap().cyan());
-                        files.push(path.path().display().to_string());
-                        count += 1;
-                    },
-                    Err(_) => println!("{}", "  Error".red())
-                };
-            }
-            println!();
-            let input = input_num(1, count - 1);
-            
-            let file = BufReader::new(File::open(&files[input as usize - 1]).expect("open failed"));
-
-            for line in file.lines() {
-                let line = line.unwrap();
-                let parts: Vec<&str> = line.split(char::from(31)).collect();
-
-                if parts.len() != 7 {continue;}
-
-                songs.push(Song {
-                    artist: parts[0].to_string(),
-                    title: parts[1].to_string(), 
-                    release_year: parts[2].parse::<i32>().unwrap(), 
-                    youtube_year: parts[3].parse::<i32>().unwrap(), 
-                    video_id: parts[4].to_string(), 
-                    raw_title: parts[5].to_string(), 
-                    detected_title: parse_option_string(parts[6])
-                });
-            }
-            
-        }
-        break;
-    }
-    
-    let mut page = 0;
-    let mut elements_per_page = 20;
-    'outer: loop {
-        let page_count = f32::ceil(songs.len() as f32 / elements_per_page as f32) as u32;
-
-        let page_str = format!("Page {}/{}", page + 1, page_count);
-        println!();
-        println!("{}", page_str.green());
-        let elements_displayed = draw_table(&songs, page, elements_per_page);
-        println!();
-        println!("Actions:");
-        println!("{}", "Number to select element".cyan());
-        println!("{}", "a/d to change page".cyan());
-        println!("{}", "+/- to change number of elements per page".cyan());
-        println!("{}", "y to finish".cyan());
-        println!();
-        print_input_arrow();
-        let input: String = read!("{}\n");
-        match input.parse::<u32>() {
-            Ok(num) => {
-                loop {
-                    if num > min(elements_displayed, elements_per_page) || num < 1 {
-                        continue 'outer;
-                    }
-                    let selected = songs.get_mut(((num - 1) + (page * elements_per_page)) as usize).unwrap();
-                    println!("Selected:");
-                    println!("Title for card:  {} - {}", selected.artist.bright_green(), selected.title.bright_green());
-                    if let Some(title) = &selected.detected_title {
-                        println!("Detected title:  {}", title.bright_green());
-                    }
-                    println!("Year:            {}", selected.release_year.to_string().bright_green());
-                    println!();
-                    println!("Actions:");
-                    println!("{} {}", "1".blue(), "New query".cyan());
-                    println!("{} {}", "2".blue(), "Change artist".cyan());
-                    println!("{} {}", "3".blue(), "Change title".cyan());
-                    println!("{} {}", "4".blue(), "Change year".cyan());
-                    println!("{} {}{}{}", "5".blue(), "Switch to YouTube year (".cyan(), selected.youtube_year.to_string().blue(), ")".cyan());
-                    println!("{} {}", "6".blue(), "Back".cyan());
-                    println!();
-                    let action = input_num(1, 6);
-                    match action {
-                        1 => {
-                            match custom_query(&client, selected).await {
-                                Ok(_) => (),
-                                Err(_) => continue,
-                            }
-                        },
-                        2 => {
-                            println!("    {}", selected.artist.blue());
-                            print_input_arrow();
-                            selected.artist = read!("{}\n");
-                        },
-                        3 => {
-                            println!("    {}", selected.title.blue());
-                            print_input_arrow();
-                            selected.title = read!("{}\n");
-                        },
-                        4 => {
-                            println!("    {}", selected.release_year.to_string().blue());
-                            selected.release_year = input_num(i32::MIN, i32::MAX);
-                        },
-                        5 => {
-                            selected.release_year = selected.youtube_year;
-                            println!("Using {} for {}", selected.release_year.to_string().blue(), selected.raw_title.green());
-                        },
-                        6 => continue 'outer,
-                        _ => return Err("unknown input".into()),
-                    }
-                }
-            },
-            Err(_) => {
-                match input.as_str() {
-                    "a" => {
-                        page = page.saturating_sub(1);
-                    },
-                    "d" => {
-                        if page < page_count - 1 {
-                            page += 1;
-                        }
-                    },
-                    "+" => {
-                        elements_per_page += 10;
-                    },
-                    "-" => {
-                        if elements_per_page > 10 {
-                            elements_per_page -= 10;
-                        }
-                    },
-                    "y" => break,
-                    _ => continue,
-                }
-            }
+impl LayoutArgs {
+    fn to_layout(&self) -> PageLayout {
+        let (width, height) = self.paper.dimensions();
+        PageLayout {
+            paper_width: self.paper_width.unwrap_or(width),
+            paper_height: self.paper_height.unwrap_or(height),
+            margin: self.margin,
+            card_size: self.card_size,
+            gutter: self.gutter,
+            bleed: self.bleed,
+            back_offset_x: self.back_offset_x,
+            back_offset_y: self.back_offset_y,
+            back_rotation: self.back_rotation,
         }
     }
+}
 
-    songs.sort_by(|a, b| i32::cmp(&a.release_year, &b.release_year));
-
-    info!("Saving List...");
-
-    let file_name = format!("song-list-{}", chrono::Local::now().format("%Y-%m-%d-%H:%M:%S"));
-    let mut song_list_file = File::create(format!("./Carnister/song_lists/{}.txt", file_name))?;
-    for song in &songs {
-        writeln!(song_list_file, "{}", song)?;
-    }
-
-    info!("Generating cards...");
-
-    let font_data = std::fs::read("./CalSans-SemiBold.ttf")
-        .expect("Error reading font file");
-    let font = Font::try_from_vec(font_data).expect("Failed to load font");
+#[derive(Args)]
+struct ImportArgs {
+    /// What to do with songs no provider could find or only matched uncertainly
+    #[arg(long, value_enum, default_value_t = YearPolicy::Ask)]
+    year_policy: YearPolicy,
 
-    let icon = std::fs::read("./icon.svg").expect("Error reading icon file").iter().fold(String::new(), |a, b| a + &(*b as char).to_string());
-    let background_design = std::fs::read("./Carnister/designs/design0.svg").expect("Error reading design file").iter().fold(String::new(), |a, b| a + &(*b as char).to_string());
+    /// Matches with a lower confidence (0 to 1) are treated like songs that were not found
+    #[arg(long, default_value_t = DEFAULT_MIN_CONFIDENCE)]
+    min_confidence: f32,
 
-    let mut pages: Vec<String> = Vec::new();
-    while !songs.is_empty() {
-        let mut card_songs: Vec<Song> = Vec::new();
-        for _ in 0..i32::min(songs.len() as i32, 12) {
-            card_songs.push(songs.pop().unwrap());
-        }
+    /// Render cards right after saving the song list
+    #[arg(long)]
+    render: bool,
 
-        let (front, back) = create_card_page(&card_songs, &font, &icon, &background_design);
-        pages.push(front);
-        pages.push(back);
-    }
-    
-    for (index, page) in pages.into_iter().enumerate() {
-        let mut output_file = File::create(format!("./Carnister/output/{}_{}.svg", file_name, index))?;
-        writeln!(output_file, "{}", page.clone())?;
-    }
-
-    Ok(())
+    /// Start over instead of resuming an interrupted import of the same playlist
+    #[arg(long)]
+    restart: bool,
 }
 
-async fn create_folder_structure_idempotent() -> Result<(), Box<dyn std::error::Error>> {
-    fs::create_dir_all("./Carnister/song_lists").await?;
-    fs::create_dir_all("./Carnister/designs").await?;
-    fs::create_dir_all("./Carnister/output").await?;
-    if !fs::try_exists("./Carnister/youtube_api_key.txt").await? {
-        fs::write("./Carnister/youtube_api_key.txt", "").await?;
-    }
-    if !fs::try_exists("./Carnister/designs/design0.svg").await? {
-        fs::copy("./design0.svg", "./Carnister/designs/design0.svg").await?;
-    }
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// Import a YouTube playlist, resolve release years and save the song list
+    ///
+    /// Without a configured API key the public playlist page is read instead.
+    ImportYoutube {
+        /// Playlist link or id, or an exported playlist JSON file (API response, Takeout or `yt-dlp -J`)
+        playlist: String,
+
+        #[command(flatten)]
+        import: ImportArgs,
+    },
+    /// Import a Spotify playlist, resolve release years and save the song list
+    ImportSpotify {
+        /// Playlist link, URI or id
+        playlist: String,
+
+        #[command(flatten)]
+        import: ImportArgs,
+
+        /// Base URL of the Spotify Web API, e.g. to test against a mock server
+        #[arg(long, default_value = spotify::API_URL)]
+        spotify_api_url: String,
+
+        /// Base URL of the Spotify accounts service that hands out access tokens
+        #[arg(long, default_value = spotify::ACCOUNTS_URL)]
+        spotify_accounts_url: String,
+    },
+    /// Import a local music folder or M3U/XSPF playlist, resolve missing release years and save the song list
+    ImportLocal {
+        /// Directory searched recursively for audio files, or a .m3u, .m3u8 or .xspf playlist
+        path: String,
+
+        #[command(flatten)]
+        import: ImportArgs,
+
+        /// Where the folder (or the playlist's folder) is served, cards link to the files below it.
+        /// Defaults to `carnister serve` on this machine.
+        #[arg(long)]
+        base_url: Option<String>,
+
+        /// Port of `carnister serve` for the default base URL
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Serve the audio files of a local music folder so the cards of an import can be played
+    Serve {
+        dir: PathBuf,
+
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Review and edit a saved song list in the table view
+    Review {
+        song_list: PathBuf,
+
+        /// Render cards right after saving the song list
+        #[arg(long)]
+        render: bool,
+    },
+    /// Review and edit a saved song list in the browser, changes are saved to the file right away
+    ReviewWeb {
+        song_list: PathBuf,
+
+        #[arg(long, default_value_t = DEFAULT_REVIEW_PORT)]
+        port: u16,
+
+        /// Accept connections from other devices in the network, not only from this computer
+        #[arg(long)]
+        lan: bool,
+
+        /// Render cards after the review is finished
+        #[arg(long)]
+        render: bool,
+    },
+    /// Render cards from a saved song list
+    Render {
+        song_list: PathBuf,
+    },
+    /// Render a duplex test sheet for measuring the back offsets and rotation
+    Calibrate,
+    /// Query MusicBrainz for a song and print all results
+    Lookup {
+        artist: String,
+        title: String,
+
+        /// Try an exact lookup by ISRC before the text query
+        #[arg(long)]
+        isrc: Option<String>,
+    },
+    /// List saved song lists
+    List,
+    /// Convert legacy .txt song lists to the versioned format
+    Migrate,
+    /// Export a saved song list as CSV for editing in a spreadsheet
+    ExportCsv {
+        song_list: PathBuf,
+
+        /// Defaults to the song list path with a .csv extension
+        output: Option<PathBuf>,
+    },
 }
 
-fn create_card_page(songs: &[Song], year_font: &Font, icon: &str, background_design: &str) -> (String, String) {
-
-    const CARD_SIZE: u32 = 65; //in mm
-
-    let mut front: Vec<String> = Vec::new();
-    
-    front.push("<svg viewBox=\"0 0 210 297\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\">".into());
-    
-    for (index, song) in songs.iter().enumerate() {
-        let x = (index as u32 % 3) * CARD_SIZE;
-        let y = (index as u32 / 3) * CARD_SIZE;
-        front.push(format!("<svg x=\"{}\" y=\"{}\" width=\"{CARD_SIZE}\" height=\"{CARD_SIZE}\">", x, y));
-        front.push(create_card_front_svg_component(song, year_font, icon, background_design));
-        front.push("</svg>".into());
-    }
-
-    front.push("</svg>".into());
-
-    let front = front.iter().fold(String::new(), |a, b| a + b + "\n");
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
 
-    let mut back: Vec<String> = Vec::new();
+    let cli = Cli::parse();
 
-    back.push("<svg viewBox=\"0 0 210 297\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\">".into());
+    let logger =
+        Builder::from_env(Env::default().default_filter_or("info"))
+        .format(|buf, record| {
 
-    for (index, song) in songs.iter().enumerate() {
-        let x = 210 - CARD_SIZE - ((index as u32 % 3) * CARD_SIZE);
-        let y = (index as u32 / 3) * CARD_SIZE;
-        back.push(format!("<svg x=\"{}\" y=\"{}\" width=\"{CARD_SIZE}\" height=\"{CARD_SIZE}\">", x, y));
+            let level = match record.level() {
+                log::Level::Info  => "INFO ".green(),
+                log::Level::Warn  => "WARN ".yellow(),
+                log::Level::Error => "ERROR".red(),
+                log::Level::Debug => "DEBUG".blue(),
+                log::Level::Trace => "TRACE".blue(),
+            };
 
-        let link = format!("https://music.youtube.com/watch?v={}", song.video_id);
-        
-        let mut qr = qrcode_generator::to_svg_to_string(link, QrCodeEcc::Low, CARD_SIZE as usize, None::<&str>).unwrap();
-        let qr = qr.split_off(qr.find("<path").unwrap());
-        let qr = qr.trim_end_matches("</svg>");
+            let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
+            writeln!(buf, "{}{} {}{} {}", "[".truecolor(150, 150, 150), timestamp.truecolor(150, 150, 150), level, "]".truecolor(150, 150, 150), record.args())
+        })
+        .build();
+    let level = logger.filter();
+    let multi = MultiProgress::new();
 
-        back.push(qr.to_owned());
+    LogWrapper::new(multi.clone(), logger)
+        .try_init()
+        .unwrap();
+    log::set_max_level(level);
 
-        back.push("</svg>".into());
+    if let Err(e) = run(cli, &multi).await {
+        error!("{}", e);
+        exit(1);
     }
 
-    back.push("</svg>".into());
-
-    let back = back.iter().fold(String::new(), |a, b| a + b + "\n");
-
-    (front, back)
-}
-
-fn parse_option_string(input: &str) -> Option<String> {
-    if input.starts_with("Some(") && input.ends_with(")") {
-        let inner = &input[6..input.len() - 2];
-        return Some(inner.to_string());
-    } else if input == "None" {
-        return None;
-    }
-    None
+    Ok(())
 }
 
-fn create_card_front_svg_component(song: &Song, font: &Font, icon: &str, bg_design: &str) -> String {
-    
-    let mut svg = Vec::new();
-
-    let year = Text::builder().size(30.0).start(Point {x: 0.0, y: 0.0}).build(font, &song.release_year.to_string());
-    let year_x = (100.0 - year.bounding_box.width()) / 2.0;
-    let year_y = (100.0 - year.bounding_box.height()) / 2.0 - year.bounding_box.height() / 5.0;
-
-    // with font: style=\"direction:rtl ;font-family:CaskaydiaCove Nerd Font\"
-
-    let artist = format!("<text x=\"50%\" y=\"10\" dominant-baseline=\"middle\" text-anchor=\"middle\" font-size=\"5\">{}</text>", song.artist.replace("&", "&amp;").replace("'", "&apos;"));
-    let artist_x = 0.0;
-    let artist_y = 5.0;
-
-    let title = format!("<text x=\"50%\" y=\"10\" dominant-baseline=\"middle\" text-anchor=\"middle\" font-size=\"5\">{}</text>", song.title.replace("&", "&amp;").replace("'", "&apos;"));
-    let title_x = 0.0;
-    let title_y = 77.0;
-
-    let detection_prefix = "=\"";
-    let bg_replace_color1 = detection_prefix.to_string() + "#ff0000";
-    let bg_replace_color2 = detection_prefix.to_string() + "#0000ff";
-
-    let number_colors = [
-        "#00202e",
-        "#2c4875",
-        "#8a508f",
-        "#bc5090",
-        "#ff6361",
-        "#ff8531",
-        "#ffa600",
-        "#80d353",
-        "#609f3f",
-        "#406a2a",
-    ];
-
-    let n10 = (song.release_year % 100 / 10) as usize;
-    let n1 = (song.release_year % 10) as usize;
-
-    let wrong_id_bg = bg_design.replace(&bg_replace_color1, &(detection_prefix.to_string() + number_colors[n10])).replace(&bg_replace_color2, &(detection_prefix.to_string() + number_colors[n1]));
-    let mut bg = wrong_id_bg.clone();
-    let gradient_starts: Vec<(usize, &str)> = wrong_id_bg.match_indices("id=\"").collect();
-    for (index, _) in gradient_starts {
-        let start = index + 4;
-        let end = start + wrong_id_bg[start..].find("\"").expect("invalid svg gradient");
-        let id = &wrong_id_bg[start..end];
-        
-        bg = bg.replace(id, &song.video_id);
-    }
-
-    svg.push("<svg viewBox=\"0 0 100 100\">".into());
-
-    svg.push(bg);
-
-    svg.push(format!("<svg x=\"{}\" y=\"{}\">", year_x, year_y));
-    svg.push(year.path.to_string());
-    svg.push("</svg>".into());
-
-    svg.push(format!("<svg x=\"{}\" y=\"{}\">", artist_x, artist_y));
-    svg.push(artist);
-    svg.push("</svg>".into());
-
-    svg.push(format!("<svg x=\"{}\" y=\"{}\">", title_x, title_y));
-    svg.push(title);
-    svg.push("</svg>".into());
+async fn run(cli: Cli, multi: &MultiProgress) -> Result<(), Box<dyn Error>> {
 
-    svg.push("<svg x=\"3\" y=\"3\" width=\"10\" height=\"10\" viewBox=\"0 0 100 100\">".into());
-    svg.push(icon.to_owned());
-    svg.push("</svg>".into());
+    let config = config::init(cli.config.as_deref())?;
 
-    svg.push("</svg>".into());
-
-    svg.iter().fold(String::new(), |a, b| a + b + "\n")
-}
+    if let Err(e) = create_folder_structure_idempotent().await {
+        println!("Creating folder structure was unsuccessful");
+        return Err(e);
+    }
 
-async fn custom_query(client: &Client, song: &mut Song) -> Result<(), Box<dyn Error>> {
-    println!("Artist:");
-    print_input_arrow();
-    let custom_query_artist: String = read!("{}\n");
-    println!("Title:");
-    print_input_arrow();
-    let custom_query_title: String = read!("{}\n");
-    match get_music_braiz_results(client, &custom_query_artist, &custom_query_title).await {
-        Ok(results) => {
-            println!();
-            for (index, (year, detected_title, disambiguation)) in results.iter().enumerate() {
-                let d = match disambiguation {Some(d) => d, None => ""};
-                println!("{} {}", (index + 1).to_string().blue(), (year.to_string() + ": " + detected_title + "; " + d).cyan());
+    let client = Client::new();
+    let metadata = MetadataLookup::new(&cli.providers, cli.strategy, &config.cache_dir, Duration::from_secs(cli.cache_ttl_days * 24 * 60 * 60), cli.refresh)?;
+
+    let render_options = RenderOptions {
+        output_dir: cli.output_dir.unwrap_or_else(|| config.output_dir.clone()),
+        design: cli.design.unwrap_or_else(|| config.design.clone()),
+        format: cli.format,
+        layout: cli.layout.to_layout(),
+        crop_marks: cli.layout.crop_marks,
+        cut_lines: cli.layout.cut_lines,
+    };
+
+    match cli.command {
+        Some(Command::ImportYoutube { playlist, import }) => {
+            let source = YouTube::new(config.youtube_api_key.clone());
+            run_import(&client, &metadata, multi, &source, &playlist, import, &render_options).await?;
+        },
+        Some(Command::ImportSpotify { playlist, import, spotify_api_url, spotify_accounts_url }) => {
+            let source = Spotify::new(&spotify_api_url, &spotify_accounts_url)?;
+            run_import(&client, &metadata, multi, &source, &playlist, import, &render_options).await?;
+        },
+        Some(Command::ImportLocal { path, import, base_url, port }) => {
+            let source = local_source(Path::new(&path), &base_url.unwrap_or_else(|| default_base_url(port)));
+            run_import(&client, &metadata, multi, source.as_ref(), &path, import, &render_options).await?;
+        },
+        Some(Command::Serve { dir, port }) => serve_folder(&dir, port)?,
+        Some(Command::Review { song_list, render }) => {
+            let (mut songs, edits) = load_song_list_with_edits(&song_list)?;
+            let mut log = EditLog::new(edits);
+            review(&client, &metadata, &mut songs, &mut log, cli.plain).await?;
+            let file_name = save_song_list_with_edits(&songs, log.edits())?;
+            if render {
+                render_cards(songs, &file_name, &render_options)?;
             }
-            println!("{} {}", (results.len() + 1).to_string().blue(), "Back".cyan());
-            println!();
-            let input = input_num(1, results.len() as i32 + 1);
-            if input == results.len() as i32 + 1 {
-                return Err("Back".into())
+        },
+        Some(Command::ReviewWeb { song_list, port, lan, render }) => {
+            let (mut songs, edits) = load_song_list_with_edits(&song_list)?;
+            let mut log = EditLog::new(edits);
+            let address = SocketAddr::from((if lan { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST }, port));
+            review_songs_web(&client, &metadata, &mut songs, &mut log, &song_list, &render_options.design, address).await?;
+            if render {
+                let file_name = song_list.file_stem().and_then(|s| s.to_str()).unwrap_or("cards").to_string();
+                render_cards(songs, &file_name, &render_options)?;
             }
-            song.release_year = results[input as usize - 1].0;
-            song.detected_title = Some(results[input as usize - 1].1.clone());
         },
-        Err(_) => {
-            info!("{}", "Song not found".red());
-            return Err("Song not found".into());
-        }
+        Some(Command::Render { song_list }) => {
+            let songs = load_song_list(&song_list)?;
+            let file_name = song_list.file_stem().and_then(|s| s.to_str()).unwrap_or("cards").to_string();
+            render_cards(songs, &file_name, &render_options)?;
+        },
+        Some(Command::Calibrate) => render_calibration(&render_options)?,
+        Some(Command::Lookup { artist, title, isrc }) => {
+            match metadata.lookup(&client, &artist, &title, isrc.as_deref()).await {
+                Ok(results) => {
+                    for result in results {
+                        let d = result.disambiguation.unwrap_or_default();
+                        println!("{} {}", result.year.to_string().blue(), (result.detected_title + "; " + &d).cyan());
+                        println!("     {}", result.justification.truecolor(100, 100, 100));
+                    }
+                },
+                Err(_) => {
+                    error!("{}", "Song not found".red());
+                    exit(1);
+                }
+            }
+        },
+        Some(Command::List) => {
+            for path in list_song_lists()? {
+                println!("{}", path.display());
+            }
+        },
+        Some(Command::Migrate) => {
+            let migrated = migrate_legacy_song_lists()?;
+            info!("Migrated {} song lists", migrated.len());
+        },
+        Some(Command::ExportCsv { song_list, output }) => {
+            let songs = load_song_list(&song_list)?;
+            let output = output.unwrap_or_else(|| song_list.with_extension("csv"));
+            write_csv_song_list(&output, &songs)?;
+            info!("Exported {} songs to {}", songs.len(), output.display());
+        },
+        None => interactive_menu(&client, &metadata, multi, &render_options, cli.plain).await?,
     }
+
     Ok(())
 }
 
-fn draw_table(elements: &[Song], page: u32, elements_per_page: u32) -> u32 {
-
-    let longest_artist = elements.iter().map(|s| s.artist.len()).max().unwrap_or(0) as u32;
-    let longest_title = elements.iter().map(|s| s.title.len()).max().unwrap_or(0) as u32;
-    let longest_detected_title = elements.iter().map(|s| s.detected_title.clone().unwrap_or("".to_string()).len()).max().unwrap_or(0) as u32;
-    let longest_year = elements.iter().map(|s| s.release_year.to_string().len()).max().unwrap_or(0) as u32;
+async fn run_import(client: &Client, metadata: &MetadataLookup, multi: &MultiProgress, source: &dyn SongSource, playlist: &str, args: ImportArgs, render_options: &RenderOptions) -> Result<(), Box<dyn Error>> {
 
-    let mut displayed_songs: Vec<Option<&Song>> = Vec::new();
-    for i in 0..elements_per_page {
-        displayed_songs.push(elements.get((i + (page * elements_per_page)) as usize));
-    }
+    let options = ImportOptions { year_policy: args.year_policy, min_confidence: args.min_confidence };
 
-    let displayed_songs_count = displayed_songs.iter().filter(|s| s.is_some()).count() as u32;
+    let Some(playlist_id) = source.parse_playlist_id(playlist) else {
+        return Err("Invalid playlist link".into());
+    };
 
-    const TABLE_R: u8 = 100;
-    const TABLE_G: u8 = TABLE_R;
-    const TABLE_B: u8 = TABLE_R;
-
-    print!("{}", "┌────┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_artist + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_title + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_detected_title + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_year + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    println!("{}", "┐".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    print!("{}", "│ ## │ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    print!("{}", "Artist".to_string().green());
-    fillup_spaces("Artist".to_string(), longest_artist + 1);
-    print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    print!("{}", "Title".to_string().green());
-    fillup_spaces("Title".to_string(), longest_title + 1);
-    print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    print!("{}", "Detected Title".to_string().green());
-    fillup_spaces("Detected Title".to_string(), longest_detected_title + 1);
-    print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    print!("{}", "Year".to_string().green());
-    fillup_spaces("Year".to_string(), longest_year + 1);
-    println!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-
-    print!("{}", "├────┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_artist + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_title + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_detected_title + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_year + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    println!("{}", "┤".truecolor(TABLE_R, TABLE_G, TABLE_B));
-
-    let mut num = 1;
-    for song in displayed_songs {
-        
-        let artist;
-        let title;
-        let detected_title;
-        let year;
-        let base_detected_title;
-        match song {
-            Some(song) => {
-                artist = song.artist.clone();
-                title = song.title.clone();
-
-                detected_title = match song.detected_title.clone() {
-                    Some(mut raw_detected_title) => {
-
-                        base_detected_title = raw_detected_title.clone();
-                
-                        let detected_title_copy = raw_detected_title.clone();
-                        let (artist_split, title_split) = detected_title_copy.split_once(" - ").unwrap();
-                        let artists: Vec<&str> = artist_split.split(", ").collect();
-
-                        for det_artist in artists {
-                            if let Some((left, right)) = raw_detected_title.split_once(det_artist)
-                                && artist.to_lowercase().contains(det_artist.to_lowercase().as_str()) {
-                                    raw_detected_title = left.to_string() + &det_artist.green().to_string() + right;
-                                }
-                        }
-                        if let Some((left, right)) = raw_detected_title.split_once(title_split)
-                            && title.to_lowercase().contains(title_split.to_lowercase().as_str()) {
-                                raw_detected_title = left.to_string() + &title_split.green().to_string() + right;
-                            }
-
-                        raw_detected_title.blue().to_string()
-                    },
-                    None => {
-                        base_detected_title = String::new();
-                        String::new()
-                    }
-                };
-
-                year = song.release_year.to_string();
-            },
-            None => {
-                artist = String::new();
-                title = String::new();
-                detected_title = String::new();
-                year = String::new();
-                base_detected_title = String::new();
-            },
-        };
-        let num_str = (if num <= 9 {" ".to_string()} else {"".to_string()}) + &num.to_string();
-        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-        print!("{}{}", num_str.blue(), " │ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-        print!("{}", artist.green());
-        fillup_spaces(artist, longest_artist + 1);
-        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-        print!("{}", title.green());
-        fillup_spaces(title, longest_title + 1);
-        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-        print!("{}", detected_title.green());
-        fillup_spaces(base_detected_title, longest_detected_title + 1);
-        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-        print!("{}", year.green());
-        fillup_spaces(year, longest_year + 1);
-        println!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
-
-        num += 1;
+    let mut songs = match ImportCheckpoint::load() {
+        Some(checkpoint) if checkpoint.source == source.kind() && checkpoint.playlist_id == playlist_id && !args.restart => {
+            info!("Resuming interrupted import ({}/{} songs looked up)", checkpoint.processed, checkpoint.tracks.len());
+            resume_import(client, metadata, multi, checkpoint, options).await?
+        },
+        Some(checkpoint) if checkpoint.source != source.kind() || checkpoint.playlist_id != playlist_id => {
+            warn!("Discarding interrupted import of {} playlist {}", checkpoint.source.name(), checkpoint.playlist_id);
+            import_playlist(client, metadata, multi, source, playlist_id, options).await?
+        },
+        _ => import_playlist(client, metadata, multi, source, playlist_id, options).await?,
+    };
+
+    let mut log = EditLog::default();
+    if options.year_policy == YearPolicy::Ask {
+        resolve_duplicates(&mut songs, &mut log);
+    } else {
+        let duplicates = find_duplicates(&songs);
+        if !duplicates.is_empty() {
+            let count: usize = duplicates.iter().map(|g| g.songs.len()).sum();
+            warn!("Found {} groups of duplicates with {} songs, merge or drop them with `carnister review`", duplicates.len(), count);
+        }
     }
 
-    print!("{}", "└────┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_artist + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_title + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_detected_title + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    print!("{}", "┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    for _ in 0..longest_year + 2 {
-        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
-    }
-    println!("{}", "┘".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    let file_name = save_song_list_with_edits(&songs, log.edits())?;
+    ImportCheckpoint::remove()?;
 
-    displayed_songs_count
+    if args.render {
+        render_cards(songs, &file_name, render_options)?;
+    }
 
+    Ok(())
 }
 
-fn fillup_spaces(string: String, length: u32) {
-    for _ in (string.chars().count() as u32)..length {
-        print!(" ");
+/// Playlist files are imported entry by entry, anything else is searched for audio files.
+fn local_source(path: &Path, base_url: &str) -> Box<dyn SongSource> {
+    if PlaylistFile::is_playlist_file(path) {
+        Box::new(PlaylistFile::new(base_url))
+    } else {
+        Box::new(LocalFolder::new(base_url))
     }
 }
 
-fn print_input_arrow() {
-    print!("{}", "==> ".green());
-}
-
-fn input_num(range_min: i32, range_max: i32) -> i32 {
-    loop {
-        print_input_arrow();
-        let raw_input: String = read!("{}\n");
-        if let Ok(i) = raw_input.parse::<i32>()
-            && i >= range_min && i <= range_max {
-                return i;
-            };
+/// The full-screen review if the output is a terminal, the prompts otherwise.
+async fn review(client: &Client, metadata: &MetadataLookup, songs: &mut Vec<Song>, log: &mut EditLog, plain: bool) -> Result<(), Box<dyn Error>> {
+    if plain || !std::io::stdout().is_terminal() {
+        review_songs(client, metadata, songs, log).await
+    } else {
+        review_songs_tui(client, metadata, songs, log).await
     }
 }
 
-async fn fetch_videos(api_key: &str, playlist_id: &str) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let mut videos = Vec::new();
-    let mut page_token = String::new();
+const INTERACTIVE_IMPORT: ImportOptions = ImportOptions { year_policy: YearPolicy::Ask, min_confidence: DEFAULT_MIN_CONFIDENCE };
 
-    loop {
-        
-        let url = format!(
-            "https://youtube.googleapis.com/youtube/v3/playlistItems?part=snippet&part=contentDetails&maxResults=50&playlistId={}&pageToken={}&key={}",
-            playlist_id, page_token, api_key
-        );
-
-        let json = receive_json(&client, &url).await.unwrap();
+async fn interactive_menu(client: &Client, metadata: &MetadataLookup, multi: &MultiProgress, render_options: &RenderOptions, plain: bool) -> Result<(), Box<dyn Error>> {
 
-        if let Some(items) = json["items"].as_array() {
-            videos.extend(items.clone());
-        }
+    let mut checkpoint = ImportCheckpoint::load();
 
-        if let Some(next_page_token) = json["nextPageToken"].as_str() {
-            page_token = next_page_token.to_string();
-        } else {
-            break;
-        }
+    println!();
+    println!("Actions:");
+    println!();
+    if let Some(checkpoint) = &checkpoint {
+        println!("{}{}{}{}{}{}", "0 ".blue(), format!("Resume interrupted import of {} playlist ", checkpoint.source.name()).cyan(), checkpoint.playlist_id.blue(), " (".cyan(), format!("{}/{}", checkpoint.processed, checkpoint.tracks.len()).blue(), ")".cyan());
     }
+    println!("{}{}", "1 ".blue(), "Load song list from YouTube playlist".cyan());
+    println!("{}{}", "2 ".blue(), "Load song list from file".cyan());
+    println!("{}{}", "3 ".blue(), "Load song list from Spotify playlist".cyan());
+    println!("{}{}", "4 ".blue(), "Load song list from local music folder or playlist file".cyan());
+    println!();
+    println!("Enter number:");
+    let input = input_num(if checkpoint.is_some() {0} else {1}, 4);
 
-    Ok(videos)
-}
-
-async fn get_music_braiz_results(client: &Client, artist: &str, title: &str) -> Result<Vec<(i32, String, Option<String>)>, Box<dyn std::error::Error>> {
+    let mut songs: Vec<Song>;
+    let mut log = EditLog::default();
 
-    let url = format!("https://musicbrainz.org/ws/2/recording?query=recording:\"{}\" AND artist:\"{}\"&fmt=json", &title, &artist);
+    loop {
+        if input == 0 {
+            songs = resume_import(client, metadata, multi, checkpoint.take().unwrap(), INTERACTIVE_IMPORT).await?;
+            resolve_duplicates(&mut songs, &mut log);
+        } else if input == 1 || input == 3 || input == 4 {
 
-    info!("{} {} {} {}", "Getting".truecolor(75, 75, 75), artist.truecolor(100, 100, 100), "-".truecolor(100, 100, 100), title.truecolor(100, 100, 100));
+            //let playlist_id = "PLP9X6Hp3ZLpOsDk3AudxA5FueNmcrQTLr";
+            //let playlist_id = "PLTUl2dTYKo6qyyf0CC5d9yQdt_oMkm-4b";
 
-    let json = receive_json(client, &url).await.unwrap();
-    let result_count = json["recordings"].as_array().unwrap().len();
+            println!("{}", if input == 4 { "Enter folder or playlist file path:" } else { "Enter playlist link or id:" });
+            print_input_arrow();
+            let path: String = read!("{}\n");
+
+            let source: Box<dyn SongSource> = match input {
+                1 => Box::new(YouTube::new(config::get().youtube_api_key.clone())),
+                3 => Box::new(Spotify::new(spotify::API_URL, spotify::ACCOUNTS_URL)?),
+                _ => {
+                    info!("Cards will link to {}, play them by running `carnister serve` on the folder", default_base_url(DEFAULT_PORT));
+                    local_source(Path::new(path.trim()), &default_base_url(DEFAULT_PORT))
+                },
+            };
 
-    if result_count == 0 {
-        return Err(format!("Song not found. Url: {}", url).into())
-    }
+            let Some(playlist_id) = source.parse_playlist_id(&path) else {
+                error!("Invalid playlist link");
+                continue;
+            };
 
-    let mut results = Vec::new();
+            songs = import_playlist(client, metadata, multi, source.as_ref(), playlist_id, INTERACTIVE_IMPORT).await?;
+            resolve_duplicates(&mut songs, &mut log);
 
-    for result in json["recordings"].as_array().unwrap() {
-        
-        if result["first-release-date"].is_null() {
-            continue;
-        }
-    
-        let raw_date = result["first-release-date"].as_str().unwrap_or("").to_string();
-    
-        let date = match raw_date.split_once("-") {
-            Some((year, _)) => match year.parse::<i32>() {
-                Ok(year) => year,
-                Err(_) => {
-                    error!("Date Parsing Error: {}, {}", raw_date, url);
-                    continue;
-                }
-            },
-            None => match raw_date.parse::<i32>() {
-                Ok(year) => year,
-                Err(_) => {
-                    error!("Date Parsing Error: {}, {}", raw_date, url);
-                    continue;
-                }
-            }
-        };
-    
-        let artists: Vec<String> = result["artist-credit"].as_array().unwrap().iter().map(|val| val["name"].as_str().unwrap_or("").to_string()).collect();
-        let detected_title = artists.iter().fold("".to_string(), |a, b| a + ", " + b).split_off(2) + " - " + json["recordings"][0]["title"].as_str().unwrap_or("");
-        let detected_title = detected_title.replace("’", "'");
-
-        let disambiguation = if !result["disambiguation"].is_null() {
-            Some(result["disambiguation"].to_string())
         } else {
-            None
-        };
+            let files = list_song_lists()?;
 
-        results.push((date, detected_title, disambiguation));
-    }
-    info!("{} {} {} {} {}", "Found:".green(), results[0].1.cyan(), "and", results.len() - 1, "more.");
-
-    results.sort();
-    
-    Ok(results)
-}
-
-async fn receive_json(client: &Client, url: &str) -> Result<Value, Box<dyn std::error::Error>> {
-
-    let url_url = Url::parse(url).unwrap_or_else(|_| panic!("Non valid url: {}", &url));
-
-    let header = HeaderValue::from_str("Carnister/1.0 (https://github.com/Asecave/Carnister/issues)").unwrap();
-    let response = client.get(url).header(USER_AGENT, header).send().await?;
+            println!("Select a file:");
+            for (index, path) in files.iter().enumerate() {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                println!("{} {}", (index + 1).to_string().blue(), name.cyan());
+            }
+            println!();
+            let input = input_num(1, files.len() as i32);
 
-    if !response.status().is_success() {
-        println!("{} request failed with status: {}", url_url.host_str().unwrap_or("json"), response.status());
-        println!("Response body: {}", response.text().await?);
-        return Err(format!("{} request failed", url_url.host_str().unwrap_or("json")).into());
-    }
-    
-    let json: Value = response.json().await?;
-    
-    if let Some(error) = json.get("error"){
-        print!("{} returned an error: {:?}", url_url.host_str().unwrap_or("json"), error);
-        return Err(format!("{} returned an error", url_url.host_str().unwrap_or("json")).into());
+            let edits;
+            (songs, edits) = load_song_list_with_edits(&files[input as usize - 1])?;
+            log = EditLog::new(edits);
+        }
+        break;
     }
 
-    Ok(json)
-}
+    review(client, metadata, &mut songs, &mut log, plain).await?;
 
-fn clean_artist(input: &str) -> String {
-    let bracket_re = Regex::new(r"\[.*?\]").unwrap();
-    let mut temp = bracket_re.replace_all(input, "").trim().to_string();
-    if let Some(index) = temp.find("-").or(temp.find("|")) {
-        temp = temp.split_off(index);
+    let file_name = save_song_list_with_edits(&songs, log.edits())?;
+    // a checkpoint that wasn't resumed stays for later
+    if input != 2 {
+        ImportCheckpoint::remove()?;
     }
-    temp.trim().to_string()
-}
 
-fn clean_title(input: &str) -> String {
-    let bracket_re = Regex::new(r"\[.*?\]").unwrap();
-    let remix_re = Regex::new(r"\([^)]*\)").unwrap();
-    let other_re = Regex::new(r"\|.*").unwrap();
-    let temp = bracket_re.replace_all(input, "").to_string();
-    let temp = remix_re
-        .replace_all(&temp, |caps: &regex::Captures| {
-            let content = &caps[0].to_lowercase();
-            if content.contains("remix") || content.contains("edit") || content.contains("vip") {
-                caps[0].to_string()
-            } else {
-                "".to_string()
-            }
-        })
-        .to_string();
-    let temp = other_re.replace_all(&temp, "").to_string();
-    let temp = temp.replace("\"", "");
-    temp.trim().to_string()
+    render_cards(songs, &file_name, render_options)
 }
@@ -0,0 +1,126 @@
+//! On-disk cache for metadata lookups, so repeated imports and review queries don't hit the network.
+
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Mutex, time::Duration};
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use super::LookupResult;
+
+/// Bumped whenever the stored results change meaning, which discards older cache files.
+const CACHE_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix timestamp in seconds
+    fetched_at: i64,
+    results: Vec<LookupResult>,
+}
+
+pub struct LookupCache {
+    path: PathBuf,
+    ttl: Duration,
+    refresh: bool,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl LookupCache {
+
+    /// Loads the cache file at `path`. A missing or unreadable file starts an empty cache.
+    /// With `refresh` set, cached entries are never returned but fresh results are still stored.
+    pub fn open(path: &Path, ttl: Duration, refresh: bool) -> Self {
+
+        let entries = match std::fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str::<CacheFile>(&content) {
+                Ok(file) if file.version == CACHE_VERSION => file.entries,
+                Ok(_) => {
+                    info!("Discarding lookup cache {} from an older version", path.display());
+                    HashMap::new()
+                },
+                Err(e) => {
+                    warn!("Ignoring unreadable lookup cache {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+
+        LookupCache { path: path.to_path_buf(), ttl, refresh, entries: Mutex::new(entries) }
+    }
+
+    /// Cached results for the query if present and younger than the TTL. An empty list means
+    /// the query was looked up before and nothing was found.
+    pub fn get(&self, artist: &str, title: &str) -> Option<Vec<LookupResult>> {
+        self.get_key(&cache_key(artist, title))
+    }
+
+    /// Cached results of an ISRC lookup, see [`LookupCache::get`].
+    pub fn get_isrc(&self, isrc: &str) -> Option<Vec<LookupResult>> {
+        self.get_key(&isrc_key(isrc))
+    }
+
+    /// Stores the results and writes the cache file.
+    pub fn insert(&self, artist: &str, title: &str, results: &[LookupResult]) {
+        self.insert_key(cache_key(artist, title), results);
+    }
+
+    pub fn insert_isrc(&self, isrc: &str, results: &[LookupResult]) {
+        self.insert_key(isrc_key(isrc), results);
+    }
+
+    fn get_key(&self, key: &str) -> Option<Vec<LookupResult>> {
+
+        if self.refresh {
+            return None;
+        }
+
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+
+        let age = chrono::Utc::now().timestamp() - entry.fetched_at;
+        if age < 0 || age as u64 >= self.ttl.as_secs() {
+            return None;
+        }
+
+        Some(entry.results.clone())
+    }
+
+    fn insert_key(&self, key: String, results: &[LookupResult]) {
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, CacheEntry { fetched_at: chrono::Utc::now().timestamp(), results: results.to_vec() });
+
+        if let Err(e) = self.write(&entries) {
+            warn!("Could not write lookup cache {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn write(&self, entries: &HashMap<String, CacheEntry>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        #[derive(Serialize)]
+        struct CacheFileRef<'a> {
+            version: u32,
+            entries: &'a HashMap<String, CacheEntry>,
+        }
+        std::fs::write(&self.path, serde_json::to_string(&CacheFileRef { version: CACHE_VERSION, entries })?)?;
+        Ok(())
+    }
+}
+
+/// Queries that only differ in case or whitespace share an entry.
+fn cache_key(artist: &str, title: &str) -> String {
+    let normalize = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    format!("{}\u{1f}{}", normalize(artist), normalize(title))
+}
+
+/// Kept apart from text queries by the separator, which no normalized query contains.
+fn isrc_key(isrc: &str) -> String {
+    format!("isrc\u{1e}{}", isrc)
+}
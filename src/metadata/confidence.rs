@@ -0,0 +1,70 @@
+//! How likely a lookup result is the song that was queried. Results found by ISRC are exact.
+//!
+//! The confidence is a weighted average of
+//! - the similarity of the queried artist to the credited artists,
+//! - the similarity of the queried title to the recording title,
+//! - the MusicBrainz search score,
+//! - how close the recording length is to the video duration, if both are known.
+
+use super::LookupResult;
+
+/// Songs whose best result scores below this go to manual review by default
+pub const DEFAULT_MIN_CONFIDENCE: f32 = 0.6;
+
+const ARTIST_WEIGHT: f32 = 0.3;
+const TITLE_WEIGHT: f32 = 0.35;
+const SEARCH_SCORE_WEIGHT: f32 = 0.15;
+const DURATION_WEIGHT: f32 = 0.2;
+
+/// Length differences up to this many seconds count as a perfect match
+const DURATION_TOLERANCE: f32 = 3.0;
+/// Length differences from this many seconds on count as no match at all
+const DURATION_MISMATCH: f32 = 30.0;
+
+/// Confidence between 0 and 1. `duration` is the length of the source video in seconds.
+pub fn match_confidence(artist: &str, title: &str, duration: Option<u64>, result: &LookupResult) -> f32 {
+
+    if result.from_isrc {
+        return 1.0;
+    }
+
+    let artist_similarity = result.artists.iter().map(|a| similarity(artist, a))
+        .chain(std::iter::once(similarity(artist, &result.artists.join(" "))))
+        .fold(0.0, f32::max);
+    let title_similarity = similarity(title, &result.title);
+    let search_score = result.score as f32 / 100.0;
+
+    let mut confidence = ARTIST_WEIGHT * artist_similarity + TITLE_WEIGHT * title_similarity + SEARCH_SCORE_WEIGHT * search_score;
+    let mut total_weight = ARTIST_WEIGHT + TITLE_WEIGHT + SEARCH_SCORE_WEIGHT;
+
+    if let (Some(duration), Some(length_ms)) = (duration, result.length_ms) {
+        let difference = (duration as f32 - length_ms as f32 / 1000.0).abs();
+        let duration_match = 1.0 - ((difference - DURATION_TOLERANCE) / (DURATION_MISMATCH - DURATION_TOLERANCE)).clamp(0.0, 1.0);
+        confidence += DURATION_WEIGHT * duration_match;
+        total_weight += DURATION_WEIGHT;
+    }
+
+    confidence / total_weight
+}
+
+/// Similarity of two names between 0 and 1, ignoring case, punctuation and whether one
+/// contains the other (e.g. a title with an added "Remastered" suffix).
+pub(crate) fn similarity(a: &str, b: &str) -> f32 {
+
+    let a = normalize(a);
+    let b = normalize(b);
+
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let contained = if a.contains(&b) || b.contains(&a) { 0.9 } else { 0.0 };
+
+    f32::max(strsim::normalized_levenshtein(&a, &b) as f32, contained)
+}
+
+/// Lowercase words without punctuation
+pub(crate) fn normalize(s: &str) -> String {
+    let s: String = s.to_lowercase().replace('&', " and ").chars().map(|c| if c.is_alphanumeric() { c } else { ' ' }).collect();
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,105 @@
+//! Release years from the Discogs database. Searching needs a personal access token, which can be
+//! generated at <https://www.discogs.com/settings/developers>.
+
+use std::error::Error;
+use colored::Colorize;
+use log::*;
+use reqwest::{header::{HeaderMap, HeaderValue, AUTHORIZATION}, Client, Url};
+use serde_json::Value;
+
+use crate::{config, http::receive_json_with_headers, BoxFuture};
+
+use super::{cache::LookupCache, LookupResult, MetadataProvider};
+
+/// Holds the token in the data directory, unless it is configured otherwise
+pub const TOKEN_FILE: &str = "discogs_token.txt";
+
+/// Discogs doesn't rate its search results, this stands in for the search score. The results are
+/// albums containing a matching track, which is less certain than a matching recording.
+const SEARCH_SCORE: u8 = 70;
+
+pub struct Discogs {
+    token: String,
+    cache: LookupCache,
+}
+
+impl Discogs {
+    pub fn new(cache: LookupCache) -> Result<Self, Box<dyn Error>> {
+        let config = config::get();
+        let Some(token) = config.discogs_token.clone() else {
+            return Err(format!("No Discogs token specified. Put your Discogs personal access token in the {} file or set CARNISTER_DISCOGS_TOKEN.", config.data_dir.join(TOKEN_FILE).display()).into());
+        };
+        Ok(Discogs { token: token.trim().to_string(), cache })
+    }
+}
+
+impl MetadataProvider for Discogs {
+
+    fn name(&self) -> &'static str {
+        "Discogs"
+    }
+
+    /// Masters, i.e. the grouping of all versions of a release, that contain the track, in Discogs'
+    /// relevance order. The year of a master is the year of its earliest release.
+    fn lookup<'a>(&'a self, client: &'a Client, artist: &'a str, title: &'a str, _isrc: Option<&'a str>) -> BoxFuture<'a, Result<Vec<LookupResult>, Box<dyn Error>>> {
+        Box::pin(async move {
+
+            if let Some(results) = self.cache.get(artist, title) {
+                info!("{} {} {} {}", "Cached ".truecolor(75, 75, 75), artist.truecolor(100, 100, 100), "-".truecolor(100, 100, 100), title.truecolor(100, 100, 100));
+                return Ok(results);
+            }
+
+            let url = Url::parse_with_params("https://api.discogs.com/database/search", &[
+                ("type", "master"),
+                ("artist", artist),
+                ("track", title),
+                ("per_page", "25"),
+            ])?;
+            // in a header rather than the query, so it doesn't end up in logged request errors
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Discogs token={}", self.token))?);
+
+            info!("{} {} {} {} {}", "Getting".truecolor(75, 75, 75), artist.truecolor(100, 100, 100), "-".truecolor(100, 100, 100), title.truecolor(100, 100, 100), "from Discogs".truecolor(75, 75, 75));
+
+            let json = receive_json_with_headers(client, url.as_str(), headers).await?;
+
+            let results: Vec<LookupResult> = json["results"].as_array().into_iter().flatten()
+                .filter(|master| !is_compilation(master))
+                .filter_map(|master| parse_master(master, title))
+                .collect();
+
+            self.cache.insert(artist, title, &results);
+
+            Ok(results)
+        })
+    }
+}
+
+fn is_compilation(master: &Value) -> bool {
+    master["format"].as_array().is_some_and(|formats| formats.iter().any(|f| f.as_str() == Some("Compilation")))
+}
+
+/// `track` is the searched track title, as the search only returns the album it is on.
+fn parse_master(master: &Value, track: &str) -> Option<LookupResult> {
+
+    let year = master["year"].as_str().and_then(|y| y.parse::<i32>().ok()).or(master["year"].as_i64().map(|y| y as i32))?;
+    if year <= 0 {
+        return None;
+    }
+
+    // master titles are "Artist - Album"
+    let full_title = master["title"].as_str().unwrap_or("");
+    let (artist, album) = full_title.split_once(" - ").unwrap_or(("", full_title));
+
+    Some(LookupResult {
+        year,
+        artists: vec![artist.to_string()],
+        title: track.to_string(),
+        detected_title: format!("{} - {}", artist, track),
+        disambiguation: Some(format!("on {}", album)),
+        justification: format!("Discogs master \"{}\" from {}", album, year),
+        score: SEARCH_SCORE,
+        length_ms: None,
+        from_isrc: false,
+    })
+}
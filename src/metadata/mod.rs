@@ -0,0 +1,295 @@
+//! Release year lookups.
+//!
+//! Every source of release years implements [`MetadataProvider`]. A [`MetadataLookup`] asks the
+//! configured providers either one after another until one finds the song ([`Strategy::Chain`]), or
+//! all of them, preferring the year most of them agree on ([`Strategy::Vote`]).
+
+pub mod cache;
+pub mod confidence;
+pub mod discogs;
+pub mod musicbrainz;
+pub mod wikidata;
+
+use std::{error::Error, path::Path, time::Duration};
+use clap::ValueEnum;
+use colored::Colorize;
+use log::*;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::BoxFuture;
+
+use self::{cache::LookupCache, discogs::Discogs, musicbrainz::MusicBrainz, wikidata::Wikidata};
+
+/// A recording matching a query.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LookupResult {
+    pub year: i32,
+    /// Credited artist names
+    pub artists: Vec<String>,
+    /// Recording title
+    pub title: String,
+    /// "artists - title" as known to the provider
+    pub detected_title: String,
+    pub disambiguation: Option<String>,
+    /// Which release the year was taken from
+    pub justification: String,
+    /// Search score of the provider from 0 to 100
+    pub score: u8,
+    /// Recording length in milliseconds
+    pub length_ms: Option<u64>,
+    /// Found by ISRC rather than a text query, so it is an exact match
+    #[serde(default)]
+    pub from_isrc: bool,
+}
+
+pub trait MetadataProvider {
+
+    fn name(&self) -> &'static str;
+
+    /// Candidates for the song, best match first. Empty if the provider doesn't know it.
+    fn lookup<'a>(&'a self, client: &'a Client, artist: &'a str, title: &'a str, isrc: Option<&'a str>) -> BoxFuture<'a, Result<Vec<LookupResult>, Box<dyn Error>>>;
+
+    /// Recordings registered under `isrc`. Providers without ISRC support find nothing.
+    fn lookup_isrc<'a>(&'a self, _client: &'a Client, _isrc: &'a str) -> BoxFuture<'a, Result<Vec<LookupResult>, Box<dyn Error>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProviderKind {
+    /// musicbrainz.org, no account needed
+    Musicbrainz,
+    /// discogs.com, needs a personal access token in discogs_token.txt or CARNISTER_DISCOGS_TOKEN
+    Discogs,
+    /// wikidata.org, only knows songs with their own item
+    Wikidata,
+}
+
+/// How the answers of several providers are combined.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Ask the providers in order and use the first one that finds the song
+    Chain,
+    /// Ask all providers and prefer the year most of their best matches agree on
+    Vote,
+}
+
+pub struct MetadataLookup {
+    providers: Vec<Box<dyn MetadataProvider>>,
+    strategy: Strategy,
+}
+
+impl MetadataLookup {
+
+    /// Each provider caches its results in its own file in `cache_dir`.
+    pub fn new(kinds: &[ProviderKind], strategy: Strategy, cache_dir: &Path, cache_ttl: Duration, refresh: bool) -> Result<Self, Box<dyn Error>> {
+
+        let mut providers: Vec<Box<dyn MetadataProvider>> = Vec::new();
+
+        for kind in kinds {
+            let cache = |name: &str| LookupCache::open(&cache_dir.join(format!("{}.json", name)), cache_ttl, refresh);
+            providers.push(match kind {
+                ProviderKind::Musicbrainz => Box::new(MusicBrainz::new(cache("musicbrainz"))),
+                ProviderKind::Discogs => Box::new(Discogs::new(cache("discogs"))?),
+                ProviderKind::Wikidata => Box::new(Wikidata::new(cache("wikidata"))),
+            });
+        }
+
+        if providers.is_empty() {
+            return Err("At least one metadata provider is needed".into());
+        }
+
+        Ok(MetadataLookup { providers, strategy })
+    }
+
+    /// Candidates for the song, the preferred one first. Fails if no provider finds it.
+    pub async fn lookup(&self, client: &Client, artist: &str, title: &str, isrc: Option<&str>) -> Result<Vec<LookupResult>, Box<dyn Error>> {
+
+        let results = match self.strategy {
+            Strategy::Chain => {
+                let mut results = Vec::new();
+                for provider in &self.providers {
+                    results = self.ask(provider.as_ref(), client, artist, title, isrc).await;
+                    if !results.is_empty() {
+                        break;
+                    }
+                }
+                results
+            },
+            Strategy::Vote => {
+                let mut answers = Vec::new();
+                for provider in &self.providers {
+                    answers.push((provider.name(), self.ask(provider.as_ref(), client, artist, title, isrc).await));
+                }
+                vote(answers)
+            },
+        };
+
+        if results.is_empty() {
+            return Err(format!("Song not found: {} - {}", artist, title).into());
+        }
+
+        info!("{} {} {} {} {}", "Found:".green(), results[0].detected_title.cyan(), "and", results.len() - 1, "more.");
+
+        Ok(results)
+    }
+
+    /// Exact matches for `isrc` from the first provider that knows it.
+    pub async fn lookup_isrc(&self, client: &Client, isrc: &str) -> Result<Vec<LookupResult>, Box<dyn Error>> {
+        for provider in &self.providers {
+            let results = provider.lookup_isrc(client, isrc).await?;
+            if !results.is_empty() {
+                return Ok(results);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// A failing provider counts as not finding the song, so the others still get their say.
+    async fn ask(&self, provider: &dyn MetadataProvider, client: &Client, artist: &str, title: &str, isrc: Option<&str>) -> Vec<LookupResult> {
+        match provider.lookup(client, artist, title, isrc).await {
+            Ok(results) => results,
+            Err(e) => {
+                error!("{} lookup failed: {}", provider.name(), e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Every provider votes for the year of its best match. The best matches voting for the winning
+/// year come first, then all remaining results in provider order. Ties go to the earlier provider.
+fn vote(answers: Vec<(&str, Vec<LookupResult>)>) -> Vec<LookupResult> {
+
+    let mut tally: Vec<(i32, Vec<&str>)> = Vec::new();
+    for (name, results) in &answers {
+        let Some(best) = results.first() else {
+            continue;
+        };
+        match tally.iter_mut().find(|(year, _)| *year == best.year) {
+            Some((_, voters)) => voters.push(*name),
+            None => tally.push((best.year, vec![*name])),
+        }
+    }
+
+    // max_by_key returns the last maximum, so search from the back to keep the earliest provider
+    let Some((winner, voters)) = tally.iter().rev().max_by_key(|(_, voters)| voters.len()).cloned() else {
+        return Vec::new();
+    };
+    let voter_count: usize = tally.iter().map(|(_, voters)| voters.len()).sum();
+
+    let mut preferred = Vec::new();
+    let mut rest = Vec::new();
+
+    for (_, results) in answers {
+        for (index, result) in results.into_iter().enumerate() {
+            if index == 0 && result.year == winner {
+                preferred.push(result);
+            } else {
+                rest.push(result);
+            }
+        }
+    }
+
+    if voter_count > 1
+        && let Some(first) = preferred.first_mut() {
+            first.justification = format!("{} ({} of {} sources agree: {})", first.justification, voters.len(), voter_count, voters.join(", "));
+        }
+
+    preferred.append(&mut rest);
+    preferred
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Finds one result per year, best first, or fails without years.
+    struct Fake {
+        name: &'static str,
+        years: Vec<i32>,
+    }
+
+    impl MetadataProvider for Fake {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn lookup<'a>(&'a self, _client: &'a Client, _artist: &'a str, _title: &'a str, _isrc: Option<&'a str>) -> BoxFuture<'a, Result<Vec<LookupResult>, Box<dyn Error>>> {
+            Box::pin(async move {
+                if self.years.is_empty() {
+                    return Err("unavailable".into());
+                }
+                Ok(self.years.iter().map(|year| result(self.name, *year)).collect())
+            })
+        }
+    }
+
+    fn result(provider: &str, year: i32) -> LookupResult {
+        LookupResult {
+            year,
+            artists: vec!["Queen".into()],
+            title: "Bohemian Rhapsody".into(),
+            detected_title: "Queen - Bohemian Rhapsody".into(),
+            disambiguation: None,
+            justification: provider.into(),
+            score: 100,
+            length_ms: None,
+            from_isrc: false,
+        }
+    }
+
+    fn summary(results: &[LookupResult]) -> Vec<(String, i32)> {
+        results.iter().map(|r| (r.justification.clone(), r.year)).collect()
+    }
+
+    #[test]
+    fn vote_prefers_the_year_most_agree_on() {
+        let answers = vec![
+            ("MusicBrainz", vec![result("MusicBrainz", 1976), result("MusicBrainz", 1975)]),
+            ("Discogs", vec![result("Discogs", 1975)]),
+            ("Wikidata", vec![result("Wikidata", 1975), result("Wikidata", 2011)]),
+        ];
+        assert_eq!(summary(&vote(answers)), [
+            ("Discogs (2 of 3 sources agree: Discogs, Wikidata)".into(), 1975),
+            ("Wikidata".into(), 1975),
+            ("MusicBrainz".into(), 1976),
+            ("MusicBrainz".into(), 1975),
+            ("Wikidata".into(), 2011),
+        ]);
+    }
+
+    #[test]
+    fn vote_ties_go_to_the_earlier_provider() {
+        let answers = vec![
+            ("MusicBrainz", Vec::new()),
+            ("Discogs", vec![result("Discogs", 1981)]),
+            ("Wikidata", vec![result("Wikidata", 1980)]),
+        ];
+        assert_eq!(summary(&vote(answers)), [("Discogs (1 of 2 sources agree: Discogs)".into(), 1981), ("Wikidata".into(), 1980)]);
+
+        // a single voter needs no note
+        assert_eq!(summary(&vote(vec![("Discogs", vec![result("Discogs", 1981)])])), [("Discogs".into(), 1981)]);
+        assert!(vote(vec![("MusicBrainz", Vec::new())]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn failing_providers_dont_vote() {
+        let lookup = |strategy| MetadataLookup {
+            providers: vec![
+                Box::new(Fake { name: "MusicBrainz", years: Vec::new() }),
+                Box::new(Fake { name: "Discogs", years: vec![1977] }),
+                Box::new(Fake { name: "Wikidata", years: vec![1977, 1978] }),
+            ],
+            strategy,
+        };
+        let client = Client::new();
+
+        let results = lookup(Strategy::Vote).lookup(&client, "Queen", "We Will Rock You", None).await.unwrap();
+        assert_eq!(summary(&results), [("Discogs (2 of 2 sources agree: Discogs, Wikidata)".into(), 1977), ("Wikidata".into(), 1977), ("Wikidata".into(), 1978)]);
+
+        let results = lookup(Strategy::Chain).lookup(&client, "Queen", "We Will Rock You", None).await.unwrap();
+        assert_eq!(summary(&results), [("Discogs".into(), 1977)]);
+    }
+}
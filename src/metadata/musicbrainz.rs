@@ -0,0 +1,181 @@
+use std::error::Error;
+use colored::Colorize;
+use log::*;
+use reqwest::{Client, StatusCode};
+
+use serde_json::Value;
+
+use crate::{http::{receive_json, StatusError}, BoxFuture};
+
+use super::{cache::LookupCache, LookupResult, MetadataProvider};
+
+/// Secondary release group types that rarely carry the original release of a song
+const REISSUE_TYPES: [&str; 3] = ["Compilation", "DJ-mix", "Mixtape/Street"];
+
+pub struct MusicBrainz {
+    cache: LookupCache,
+}
+
+impl MusicBrainz {
+    pub fn new(cache: LookupCache) -> Self {
+        MusicBrainz { cache }
+    }
+}
+
+impl MetadataProvider for MusicBrainz {
+
+    fn name(&self) -> &'static str {
+        "MusicBrainz"
+    }
+
+    /// Results in MusicBrainz relevance order, so the first one is the best matching recording rather
+    /// than whichever mismatched recording happens to have the earliest date. Served from the cache when possible.
+    ///
+    /// With an `isrc`, the recordings registered under it are returned if MusicBrainz knows any, and the
+    /// text query is only used as a fallback.
+    fn lookup<'a>(&'a self, client: &'a Client, artist: &'a str, title: &'a str, isrc: Option<&'a str>) -> BoxFuture<'a, Result<Vec<LookupResult>, Box<dyn Error>>> {
+        Box::pin(async move {
+
+            if let Some(isrc) = isrc.map(str::trim).filter(|i| !i.is_empty()) {
+                match get_isrc_results(client, &self.cache, isrc).await {
+                    Ok(results) if !results.is_empty() => {
+                        info!("{} {}", "Found by ISRC:".green(), isrc.cyan());
+                        return Ok(results);
+                    },
+                    Ok(_) => info!("No recording with ISRC {}, falling back to a text query", isrc),
+                    Err(e) => warn!("ISRC lookup for {} failed, falling back to a text query: {}", isrc, e),
+                }
+            }
+
+            if let Some(results) = self.cache.get(artist, title) {
+                info!("{} {} {} {}", "Cached ".truecolor(75, 75, 75), artist.truecolor(100, 100, 100), "-".truecolor(100, 100, 100), title.truecolor(100, 100, 100));
+                return Ok(results);
+            }
+
+            let results = query_music_brainz(client, artist, title).await?;
+            self.cache.insert(artist, title, &results);
+
+            Ok(results)
+        })
+    }
+
+    fn lookup_isrc<'a>(&'a self, client: &'a Client, isrc: &'a str) -> BoxFuture<'a, Result<Vec<LookupResult>, Box<dyn Error>>> {
+        Box::pin(get_isrc_results(client, &self.cache, isrc))
+    }
+}
+
+/// Recordings registered under `isrc`, empty if MusicBrainz doesn't know it.
+async fn get_isrc_results(client: &Client, cache: &LookupCache, isrc: &str) -> Result<Vec<LookupResult>, Box<dyn Error>> {
+
+    let isrc = &normalize_isrc(isrc)?;
+
+    if let Some(results) = cache.get_isrc(isrc) {
+        info!("{} {}", "Cached ".truecolor(75, 75, 75), isrc.truecolor(100, 100, 100));
+        return Ok(results);
+    }
+
+    let url = format!("https://musicbrainz.org/ws/2/isrc/{}?inc=artist-credits+releases+release-groups&fmt=json", isrc);
+
+    info!("{} {}", "Getting".truecolor(75, 75, 75), isrc.truecolor(100, 100, 100));
+
+    let results = match receive_json(client, &url).await {
+        Ok(json) => json["recordings"].as_array().map(|recordings| recordings.iter().filter_map(|r| parse_recording(r, true)).collect()).unwrap_or_default(),
+        // MusicBrainz answers unknown ISRCs with 404
+        Err(e) if e.downcast_ref::<StatusError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    cache.insert_isrc(isrc, &results);
+
+    Ok(results)
+}
+
+/// ISRCs are 12 alphanumeric characters, often written with dashes
+fn normalize_isrc(isrc: &str) -> Result<String, String> {
+    let code: String = isrc.chars().filter(|c| *c != '-' && !c.is_whitespace()).collect::<String>().to_uppercase();
+    if code.len() != 12 || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid ISRC: {}", isrc));
+    }
+    Ok(code)
+}
+
+async fn query_music_brainz(client: &Client, artist: &str, title: &str) -> Result<Vec<LookupResult>, Box<dyn Error>> {
+
+    let url = format!("https://musicbrainz.org/ws/2/recording?query=recording:\"{}\" AND artist:\"{}\"&fmt=json", &title, &artist);
+
+    info!("{} {} {} {}", "Getting".truecolor(75, 75, 75), artist.truecolor(100, 100, 100), "-".truecolor(100, 100, 100), title.truecolor(100, 100, 100));
+
+    let json = receive_json(client, &url).await?;
+
+    let mut results = Vec::new();
+
+    for result in json["recordings"].as_array().ok_or("MusicBrainz response contains no recordings")? {
+        results.extend(parse_recording(result, false));
+    }
+
+    Ok(results)
+}
+
+/// `None` if the recording has no usable release date.
+fn parse_recording(recording: &Value, from_isrc: bool) -> Option<LookupResult> {
+
+    let (year, justification) = earliest_release(recording)?;
+
+    let artists: Vec<String> = recording["artist-credit"].as_array().map(|credits| credits.iter().map(|val| val["name"].as_str().unwrap_or("").to_string()).collect()).unwrap_or_default();
+    let recording_title = recording["title"].as_str().unwrap_or("").to_string();
+    let detected_title = artists.join(", ") + " - " + &recording_title;
+    let detected_title = detected_title.replace("’", "'");
+
+    let disambiguation = recording["disambiguation"].as_str().filter(|d| !d.is_empty()).map(str::to_string);
+    // lookups by ISRC are exact and carry no search score
+    let score = if from_isrc { 100 } else { recording["score"].as_u64().unwrap_or(0).min(100) as u8 };
+    let length_ms = recording["length"].as_u64();
+
+    Some(LookupResult { year, artists, title: recording_title, detected_title, disambiguation, justification, score, length_ms, from_isrc })
+}
+
+/// Year of the earliest official release of a recording that isn't a compilation. Falls back to
+/// any official release and then to the recording's first release date.
+fn earliest_release(recording: &Value) -> Option<(i32, String)> {
+
+    let releases: Vec<&Value> = recording["releases"].as_array().map(|r| r.iter().collect()).unwrap_or_default();
+    let official: Vec<&Value> = releases.into_iter()
+        .filter(|r| r["status"].as_str() == Some("Official") && parse_year(r["date"].as_str().unwrap_or("")).is_some())
+        .collect();
+
+    let is_original = |release: &&Value| !release["release-group"]["secondary-types"].as_array()
+        .is_some_and(|types| types.iter().any(|t| REISSUE_TYPES.contains(&t.as_str().unwrap_or(""))));
+
+    let (release, prefix) = match earliest(official.iter().copied().filter(is_original)) {
+        Some(release) => (release, "Earliest official release"),
+        None => match earliest(official.into_iter()) {
+            Some(release) => (release, "Earliest official release (all are compilations)"),
+            None => {
+                let date = recording["first-release-date"].as_str()?;
+                let Some(year) = parse_year(date) else {
+                    error!("Date Parsing Error: {}", date);
+                    return None;
+                };
+                return Some((year, format!("First release date of the recording ({})", date)));
+            }
+        }
+    };
+
+    let date = release["date"].as_str().unwrap_or("");
+    let group = &release["release-group"];
+    let mut types: Vec<&str> = group["primary-type"].as_str().into_iter().collect();
+    types.extend(group["secondary-types"].as_array().into_iter().flatten().filter_map(Value::as_str));
+    let title = group["title"].as_str().or(release["title"].as_str()).unwrap_or("");
+
+    Some((parse_year(date)?, format!("{}: \"{}\" ({}) on {}", prefix, title, types.join(", "), date)))
+}
+
+/// Dates are ISO formatted, so they compare correctly as strings
+fn earliest<'a>(releases: impl Iterator<Item = &'a Value>) -> Option<&'a Value> {
+    releases.min_by_key(|r| r["date"].as_str().unwrap_or(""))
+}
+
+/// Year of a `YYYY`, `YYYY-MM` or `YYYY-MM-DD` date
+fn parse_year(date: &str) -> Option<i32> {
+    date.split('-').next()?.parse::<i32>().ok()
+}
@@ -0,0 +1,94 @@
+//! Release years from Wikidata. Only songs and singles with their own item are found, matched by
+//! their exact English label and the label of a performer.
+
+use std::error::Error;
+use colored::Colorize;
+use log::*;
+use reqwest::{Client, Url};
+use serde_json::Value;
+
+use crate::{http::receive_json, BoxFuture};
+
+use super::{cache::LookupCache, LookupResult, MetadataProvider};
+
+/// Exact label matches stand in for the search score, which Wikidata doesn't have
+const SEARCH_SCORE: u8 = 90;
+
+pub struct Wikidata {
+    cache: LookupCache,
+}
+
+impl Wikidata {
+    pub fn new(cache: LookupCache) -> Self {
+        Wikidata { cache }
+    }
+}
+
+impl MetadataProvider for Wikidata {
+
+    fn name(&self) -> &'static str {
+        "Wikidata"
+    }
+
+    /// Items titled `title` performed by `artist` with their earliest publication date.
+    fn lookup<'a>(&'a self, client: &'a Client, artist: &'a str, title: &'a str, _isrc: Option<&'a str>) -> BoxFuture<'a, Result<Vec<LookupResult>, Box<dyn Error>>> {
+        Box::pin(async move {
+
+            if let Some(results) = self.cache.get(artist, title) {
+                info!("{} {} {} {}", "Cached ".truecolor(75, 75, 75), artist.truecolor(100, 100, 100), "-".truecolor(100, 100, 100), title.truecolor(100, 100, 100));
+                return Ok(results);
+            }
+
+            // P175 performer, P577 publication date
+            let query = format!(
+                "SELECT ?item ?itemLabel ?performerLabel (MIN(?date) AS ?earliest) WHERE {{
+                    ?item rdfs:label \"{}\"@en; wdt:P175 ?performer; wdt:P577 ?date.
+                    ?performer rdfs:label \"{}\"@en.
+                    SERVICE wikibase:label {{ bd:serviceParam wikibase:language \"en\". }}
+                }} GROUP BY ?item ?itemLabel ?performerLabel",
+                sparql_escape(title), sparql_escape(artist)
+            );
+
+            let url = Url::parse_with_params("https://query.wikidata.org/sparql", &[("query", query.as_str()), ("format", "json")])?;
+
+            info!("{} {} {} {} {}", "Getting".truecolor(75, 75, 75), artist.truecolor(100, 100, 100), "-".truecolor(100, 100, 100), title.truecolor(100, 100, 100), "from Wikidata".truecolor(75, 75, 75));
+
+            let json = receive_json(client, url.as_str()).await?;
+
+            let mut results: Vec<LookupResult> = json["results"]["bindings"].as_array().into_iter().flatten().filter_map(parse_binding).collect();
+            results.sort_by_key(|r| r.year);
+
+            self.cache.insert(artist, title, &results);
+
+            Ok(results)
+        })
+    }
+}
+
+fn parse_binding(binding: &Value) -> Option<LookupResult> {
+
+    // dates look like 1999-03-01T00:00:00Z
+    let date = binding["earliest"]["value"].as_str()?;
+    let year = date.split('-').next()?.parse::<i32>().ok()?;
+
+    let item = binding["item"]["value"].as_str().unwrap_or("");
+    let id = item.rsplit('/').next().unwrap_or(item);
+    let title = binding["itemLabel"]["value"].as_str().unwrap_or("").to_string();
+    let artist = binding["performerLabel"]["value"].as_str().unwrap_or("").to_string();
+
+    Some(LookupResult {
+        year,
+        detected_title: format!("{} - {}", artist, title),
+        artists: vec![artist],
+        title,
+        disambiguation: None,
+        justification: format!("Wikidata {} published {}", id, date.get(..10).unwrap_or(date)),
+        score: SEARCH_SCORE,
+        length_ms: None,
+        from_isrc: false,
+    })
+}
+
+fn sparql_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " ")
+}
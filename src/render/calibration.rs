@@ -0,0 +1,83 @@
+//! Test sheet for measuring how far a printer shifts the back side in duplex mode.
+//!
+//! The front page has a cross with a millimetre scale at every card center, the back
+//! page a plain cross at the mirrored position with the current back correction applied.
+//! Print both sides, hold the sheet against a light and read where the back cross falls
+//! on each front scale. The scale labels are the values to add to the configured
+//! back offsets. If the vertical readings differ between the left and right cards, the
+//! back side is also rotated: add `(left - right) / distance × 57.3` degrees to the
+//! rotation, where `distance` is the horizontal distance between the two crosses in mm.
+
+use super::layout::PageLayout;
+
+/// Half the length of a scale, in mm
+const SCALE_RANGE: i32 = 5;
+
+pub fn create_calibration_pages(layout: &PageLayout) -> (String, String) {
+
+    let page_header = format!("<svg viewBox=\"0 0 {} {}\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\">", layout.paper_width, layout.paper_height);
+    let card_size = layout.card_size;
+    let center = card_size / 2.0;
+
+    let mut front: Vec<String> = Vec::new();
+
+    front.push(page_header.clone());
+
+    for index in 0..layout.cards_per_page() {
+        let (x, y) = layout.front_position(index);
+        front.push(format!("<rect x=\"{}\" y=\"{}\" width=\"{card_size}\" height=\"{card_size}\" fill=\"none\" stroke=\"#c0c0c0\" stroke-width=\"0.1\"/>", x, y));
+        front.push(create_scale(x + center, y + center));
+    }
+
+    front.push(format!("<text x=\"{}\" y=\"{}\" font-size=\"3\" text-anchor=\"middle\">Print double-sided, hold against a light and add the readings to the back offsets</text>", layout.paper_width / 2.0, layout.paper_height - 3.0));
+
+    front.push("</svg>".into());
+
+    let front = front.iter().fold(String::new(), |a, b| a + b + "\n");
+
+    let mut back: Vec<String> = Vec::new();
+
+    back.push(page_header);
+    back.push(layout.back_transform_start());
+
+    for index in 0..layout.cards_per_page() {
+        let (x, y) = layout.back_position(index);
+        back.push(create_cross(x + center, y + center, SCALE_RANGE as f32 + 1.0, "#ff0000", 0.15));
+    }
+
+    back.push("</g>".into());
+    back.push("</svg>".into());
+
+    let back = back.iter().fold(String::new(), |a, b| a + b + "\n");
+
+    (front, back)
+}
+
+fn create_cross(x: f32, y: f32, half_length: f32, color: &str, width: f32) -> String {
+    format!(
+        "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"{color}\" stroke-width=\"{width}\"/>\n<line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" stroke=\"{color}\" stroke-width=\"{width}\"/>",
+        x - half_length, x + half_length, y - half_length, y + half_length
+    )
+}
+
+/// Cross with ticks every mm. Seen from the front, a back shifted to the right appears
+/// shifted to the left, so horizontal labels already carry the sign of the correction.
+/// Vertical shifts are not mirrored and are labelled with the opposite sign.
+fn create_scale(x: f32, y: f32) -> String {
+
+    let mut scale = vec![create_cross(x, y, SCALE_RANGE as f32, "#000000", 0.1)];
+
+    for mm in -SCALE_RANGE..=SCALE_RANGE {
+        if mm == 0 {
+            continue;
+        }
+        let offset = mm as f32;
+        let tick = if mm % 5 == 0 { 1.5 } else { 0.8 };
+        scale.push(format!("<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"#000000\" stroke-width=\"0.1\"/>", x + offset, y - tick, y + tick));
+        scale.push(format!("<line x1=\"{1}\" y1=\"{0}\" x2=\"{2}\" y2=\"{0}\" stroke=\"#000000\" stroke-width=\"0.1\"/>", y + offset, x - tick, x + tick));
+        scale.push(format!("<text x=\"{}\" y=\"{}\" font-size=\"1.2\" text-anchor=\"middle\">{}</text>", x + offset, y - 2.0, mm));
+        scale.push(format!("<text x=\"{}\" y=\"{}\" font-size=\"1.2\" text-anchor=\"start\">{}</text>", x + 2.0, y + offset + 0.4, -mm));
+    }
+
+    scale.join("\n")
+}
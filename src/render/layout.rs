@@ -0,0 +1,169 @@
+//! Placement of cards on a printed sheet. All lengths are in mm.
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PaperSize {
+    /// 210 × 297 mm
+    A4,
+    /// 297 × 420 mm
+    A3,
+    /// 8.5 × 11 in
+    Letter,
+    /// 8.5 × 14 in
+    Legal,
+}
+
+impl PaperSize {
+    /// Width and height in mm, portrait.
+    pub fn dimensions(self) -> (f32, f32) {
+        match self {
+            PaperSize::A4 => (210.0, 297.0),
+            PaperSize::A3 => (297.0, 420.0),
+            PaperSize::Letter => (215.9, 279.4),
+            PaperSize::Legal => (215.9, 355.6),
+        }
+    }
+}
+
+/// Gap between the bleed edge and the start of a crop mark
+const CROP_MARK_OFFSET: f32 = 2.0;
+const CROP_MARK_LENGTH: f32 = 5.0;
+
+/// Square cards arranged in a grid that is centered inside the page margins.
+#[derive(Clone, Copy)]
+pub struct PageLayout {
+    pub paper_width: f32,
+    pub paper_height: f32,
+    /// Minimum distance between the paper edge and the outermost cards
+    pub margin: f32,
+    pub card_size: f32,
+    /// Space between neighbouring cards
+    pub gutter: f32,
+    /// How far each card's background extends past its trim edge
+    pub bleed: f32,
+    /// Shift of the back page content to the right, compensating duplex misregistration
+    pub back_offset_x: f32,
+    /// Shift of the back page content downwards
+    pub back_offset_y: f32,
+    /// Clockwise rotation of the back page content around the page center, in degrees
+    pub back_rotation: f32,
+}
+
+impl Default for PageLayout {
+    fn default() -> Self {
+        let (paper_width, paper_height) = PaperSize::A4.dimensions();
+        PageLayout { paper_width, paper_height, margin: 0.0, card_size: 65.0, gutter: 0.0, bleed: 0.0, back_offset_x: 0.0, back_offset_y: 0.0, back_rotation: 0.0 }
+    }
+}
+
+impl PageLayout {
+
+    pub fn columns(&self) -> u32 {
+        Self::fitting(self.paper_width - 2.0 * self.margin, self.card_size, self.gutter)
+    }
+
+    pub fn rows(&self) -> u32 {
+        Self::fitting(self.paper_height - 2.0 * self.margin, self.card_size, self.gutter)
+    }
+
+    pub fn cards_per_page(&self) -> u32 {
+        self.columns() * self.rows()
+    }
+
+    /// Fails if not a single card fits on the page.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.card_size <= 0.0 || self.gutter < 0.0 || self.margin < 0.0 || self.bleed < 0.0 {
+            return Err("Card size must be positive, margin, gutter and bleed must not be negative".into());
+        }
+        if self.bleed > 0.0 && self.gutter < 2.0 * self.bleed {
+            return Err(format!("A {} mm bleed needs a gutter of at least {} mm so neighbouring cards do not overlap", self.bleed, 2.0 * self.bleed));
+        }
+        if self.margin < self.bleed {
+            return Err(format!("A {} mm bleed needs a margin of at least {} mm", self.bleed, self.bleed));
+        }
+        if self.cards_per_page() == 0 {
+            return Err(format!("A {} mm card does not fit on a {} × {} mm page with {} mm margins", self.card_size, self.paper_width, self.paper_height, self.margin));
+        }
+        Ok(())
+    }
+
+    /// Top left corner of the card at `index` on the front page.
+    pub fn front_position(&self, index: u32) -> (f32, f32) {
+        let column = index % self.columns();
+        let row = index / self.columns();
+        let (left, top) = self.grid_origin();
+        (left + column as f32 * self.pitch(), top + row as f32 * self.pitch())
+    }
+
+    /// Top left corner of the card at `index` on the back page, mirrored horizontally
+    /// so it lands behind its front when printed double-sided along the long edge.
+    pub fn back_position(&self, index: u32) -> (f32, f32) {
+        let (x, y) = self.front_position(index);
+        (self.paper_width - self.card_size - x, y)
+    }
+
+    /// Opening tag of a group that applies the back side correction. Close it with `</g>`.
+    pub fn back_transform_start(&self) -> String {
+        format!(
+            "<g transform=\"translate({} {}) rotate({} {} {})\">",
+            self.back_offset_x, self.back_offset_y, self.back_rotation, self.paper_width / 2.0, self.paper_height / 2.0
+        )
+    }
+
+    /// Line segments `[x1, y1, x2, y2]` in the page margin that extend every trim edge
+    /// of the grid. Fails if the margin has no room for them.
+    pub fn crop_marks(&self) -> Result<Vec<[f32; 4]>, String> {
+
+        let (left, top) = self.grid_origin();
+        let right = self.paper_width - left;
+        let bottom = self.paper_height - top;
+        let offset = self.bleed + CROP_MARK_OFFSET;
+
+        if left.min(top) <= offset {
+            return Err(format!("Crop marks need a margin of more than {} mm", offset));
+        }
+
+        let mut xs: Vec<f32> = (0..self.columns()).flat_map(|column| {
+            let x = left + column as f32 * self.pitch();
+            [x, x + self.card_size]
+        }).collect();
+        xs.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+
+        let mut ys: Vec<f32> = (0..self.rows()).flat_map(|row| {
+            let y = top + row as f32 * self.pitch();
+            [y, y + self.card_size]
+        }).collect();
+        ys.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+
+        let mut marks = Vec::new();
+        for x in xs {
+            marks.push([x, top - offset, x, (top - offset - CROP_MARK_LENGTH).max(0.0)]);
+            marks.push([x, bottom + offset, x, (bottom + offset + CROP_MARK_LENGTH).min(self.paper_height)]);
+        }
+        for y in ys {
+            marks.push([left - offset, y, (left - offset - CROP_MARK_LENGTH).max(0.0), y]);
+            marks.push([right + offset, y, (right + offset + CROP_MARK_LENGTH).min(self.paper_width), y]);
+        }
+
+        Ok(marks)
+    }
+
+    fn pitch(&self) -> f32 {
+        self.card_size + self.gutter
+    }
+
+    fn grid_origin(&self) -> (f32, f32) {
+        let grid_width = self.columns() as f32 * self.pitch() - self.gutter;
+        let grid_height = self.rows() as f32 * self.pitch() - self.gutter;
+        ((self.paper_width - grid_width) / 2.0, (self.paper_height - grid_height) / 2.0)
+    }
+
+    fn fitting(available: f32, card_size: f32, gutter: f32) -> u32 {
+        if available < card_size {
+            return 0;
+        }
+        // small tolerance so exact fits are not lost to rounding
+        ((available + gutter) / (card_size + gutter) + 1e-4).floor() as u32
+    }
+}
@@ -0,0 +1,300 @@
+pub mod calibration;
+pub mod layout;
+pub mod pdf;
+
+use std::{error::Error, fs::File, io::Write, path::PathBuf};
+use clap::ValueEnum;
+use log::*;
+use qrcode_generator::QrCodeEcc;
+use rusttype::{Font, Point};
+use text_svg::Text;
+
+use crate::{config, Song};
+
+use self::{calibration::create_calibration_pages, layout::PageLayout, pdf::write_pdf};
+
+/// Output written by [`render_cards`].
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One multi-page PDF with fronts and backs interleaved for duplex printing
+    Pdf,
+    /// One SVG file per page side
+    Svg,
+}
+
+pub struct RenderOptions {
+    pub output_dir: PathBuf,
+    pub design: PathBuf,
+    pub format: OutputFormat,
+    pub layout: PageLayout,
+    /// Draw crop marks in the page margin
+    pub crop_marks: bool,
+    /// Draw a thin line along every card's trim edge
+    pub cut_lines: bool,
+}
+
+pub fn render_cards(mut songs: Vec<Song>, file_name: &str, options: &RenderOptions) -> Result<(), Box<dyn Error>> {
+
+    options.layout.validate()?;
+    if options.crop_marks {
+        options.layout.crop_marks()?;
+    }
+
+    info!("Generating cards...");
+
+    songs.sort_by(|a, b| i32::cmp(&a.release_year, &b.release_year));
+
+    let font_data = config::get().font_data()?;
+    let font = Font::try_from_vec(font_data.clone()).ok_or("Failed to load font")?;
+
+    let icon = config::get().icon_svg()?;
+    let background_design = std::fs::read_to_string(&options.design).map_err(|e| format!("Error reading design {}: {}", options.design.display(), e))?;
+
+    let mut pages: Vec<String> = Vec::new();
+    while !songs.is_empty() {
+        let mut card_songs: Vec<Song> = Vec::new();
+        for _ in 0..usize::min(songs.len(), options.layout.cards_per_page() as usize) {
+            card_songs.push(songs.pop().unwrap());
+        }
+
+        let (front, back) = create_card_page(&card_songs, options, &font, &icon, &background_design);
+        pages.push(front);
+        pages.push(back);
+    }
+
+    write_pages(&pages, file_name, font_data, options)
+}
+
+/// Writes the duplex calibration sheet for the configured layout and back correction.
+pub fn render_calibration(options: &RenderOptions) -> Result<(), Box<dyn Error>> {
+
+    options.layout.validate()?;
+
+    let font_data = config::get().font_data()?;
+
+    let (front, back) = create_calibration_pages(&options.layout);
+
+    write_pages(&[front, back], "calibration", font_data, options)
+}
+
+fn write_pages(pages: &[String], file_name: &str, font_data: Vec<u8>, options: &RenderOptions) -> Result<(), Box<dyn Error>> {
+
+    std::fs::create_dir_all(&options.output_dir)?;
+
+    match options.format {
+        OutputFormat::Pdf => {
+            let path = options.output_dir.join(format!("{}.pdf", file_name));
+            write_pdf(&path, pages, font_data, (options.layout.paper_width, options.layout.paper_height))?;
+            info!("Pages written to {}", path.display());
+        },
+        OutputFormat::Svg => {
+            for (index, page) in pages.iter().enumerate() {
+                let mut output_file = File::create(options.output_dir.join(format!("{}_{}.svg", file_name, index)))?;
+                writeln!(output_file, "{}", page)?;
+            }
+            info!("Pages written to {}", options.output_dir.display());
+        },
+    }
+
+    Ok(())
+}
+
+pub fn create_card_page(songs: &[Song], options: &RenderOptions, year_font: &Font, icon: &str, background_design: &str) -> (String, String) {
+
+    let layout = &options.layout;
+    let card_size = layout.card_size;
+    let bleed = layout.bleed;
+    let bleed_size = card_size + 2.0 * bleed;
+    // the card front is drawn in a 100 × 100 coordinate system
+    let bleed_units = bleed * 100.0 / card_size;
+    let page_header = format!("<svg viewBox=\"0 0 {} {}\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\">", layout.paper_width, layout.paper_height);
+
+    let mut front: Vec<String> = Vec::new();
+
+    front.push(page_header.clone());
+
+    for (index, song) in songs.iter().enumerate() {
+        let (x, y) = layout.front_position(index as u32);
+        front.push(format!("<svg x=\"{}\" y=\"{}\" width=\"{bleed_size}\" height=\"{bleed_size}\">", x - bleed, y - bleed));
+        front.push(create_card_front_svg_component(song, year_font, icon, background_design, bleed_units));
+        front.push("</svg>".into());
+    }
+
+    if options.cut_lines {
+        for index in 0..songs.len() {
+            let (x, y) = layout.front_position(index as u32);
+            front.push(format!("<rect x=\"{}\" y=\"{}\" width=\"{card_size}\" height=\"{card_size}\" fill=\"none\" stroke=\"#808080\" stroke-width=\"0.1\"/>", x, y));
+        }
+    }
+
+    if options.crop_marks {
+        for [x1, y1, x2, y2] in layout.crop_marks().unwrap_or_default() {
+            front.push(format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#000000\" stroke-width=\"0.2\"/>", x1, y1, x2, y2));
+        }
+    }
+
+    front.push("</svg>".into());
+
+    let front = front.iter().fold(String::new(), |a, b| a + b + "\n");
+
+    let mut back: Vec<String> = Vec::new();
+
+    back.push(page_header);
+    back.push(layout.back_transform_start());
+
+    // the QR generator only works with whole units, so it draws into its own coordinate system
+    let qr_size = card_size.round().max(1.0) as usize;
+
+    for (index, song) in songs.iter().enumerate() {
+        let (x, y) = layout.back_position(index as u32);
+        back.push(format!("<svg x=\"{}\" y=\"{}\" width=\"{card_size}\" height=\"{card_size}\" viewBox=\"0 0 {qr_size} {qr_size}\">", x, y));
+
+        back.push(create_qr_code(&song.playback_url(), qr_size));
+
+        back.push("</svg>".into());
+    }
+
+    back.push("</g>".into());
+    back.push("</svg>".into());
+
+    let back = back.iter().fold(String::new(), |a, b| a + b + "\n");
+
+    (front, back)
+}
+
+/// Front and back of one card side by side, for previews.
+pub fn create_card_preview(song: &Song, year_font: &Font, icon: &str, background_design: &str) -> String {
+
+    const QR_SIZE: usize = 100;
+
+    let mut svg = Vec::new();
+
+    svg.push("<svg viewBox=\"0 0 210 100\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\">".to_string());
+    svg.push("<svg width=\"100\" height=\"100\">".into());
+    svg.push(create_card_front_svg_component(song, year_font, icon, background_design, 0.0));
+    svg.push("</svg>".into());
+
+    svg.push("<rect x=\"110\" width=\"100\" height=\"100\" fill=\"#ffffff\" stroke=\"#808080\" stroke-width=\"0.2\"/>".into());
+    svg.push(format!("<svg x=\"115\" y=\"5\" width=\"90\" height=\"90\" viewBox=\"0 0 {QR_SIZE} {QR_SIZE}\">"));
+    svg.push(create_qr_code(&song.playback_url(), QR_SIZE));
+    svg.push("</svg>".into());
+
+    svg.push("</svg>".into());
+
+    svg.iter().fold(String::new(), |a, b| a + b + "\n")
+}
+
+/// The paths of a QR code for `link`, drawn into a `size` × `size` coordinate system.
+fn create_qr_code(link: &str, size: usize) -> String {
+    let mut qr = qrcode_generator::to_svg_to_string(link, QrCodeEcc::Low, size, None::<&str>).unwrap();
+    let qr = qr.split_off(qr.find("<path").unwrap());
+    qr.trim_end_matches("</svg>").to_owned()
+}
+
+/// Escapes text for SVG content and attribute values.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+/// `bleed` is in card units and only extends the background.
+fn create_card_front_svg_component(song: &Song, font: &Font, icon: &str, bg_design: &str, bleed: f32) -> String {
+    
+    let mut svg = Vec::new();
+
+    let year = Text::builder().size(30.0).start(Point {x: 0.0, y: 0.0}).build(font, &song.release_year.to_string());
+    let year_x = (100.0 - year.bounding_box.width()) / 2.0;
+    let year_y = (100.0 - year.bounding_box.height()) / 2.0 - year.bounding_box.height() / 5.0;
+
+    // with font: style=\"direction:rtl ;font-family:CaskaydiaCove Nerd Font\"
+
+    let artist = format!("<text x=\"50%\" y=\"10\" dominant-baseline=\"middle\" text-anchor=\"middle\" font-size=\"5\">{}</text>", xml_escape(&song.artist));
+    let artist_x = 0.0;
+    let artist_y = 5.0;
+
+    let title = format!("<text x=\"50%\" y=\"10\" dominant-baseline=\"middle\" text-anchor=\"middle\" font-size=\"5\">{}</text>", xml_escape(&song.title));
+    let title_x = 0.0;
+    let title_y = 77.0;
+
+    let detection_prefix = "=\"";
+    let bg_replace_color1 = detection_prefix.to_string() + "#ff0000";
+    let bg_replace_color2 = detection_prefix.to_string() + "#0000ff";
+
+    let number_colors = [
+        "#00202e",
+        "#2c4875",
+        "#8a508f",
+        "#bc5090",
+        "#ff6361",
+        "#ff8531",
+        "#ffa600",
+        "#80d353",
+        "#609f3f",
+        "#406a2a",
+    ];
+
+    let n10 = (song.release_year % 100 / 10) as usize;
+    let n1 = (song.release_year % 10) as usize;
+
+    let wrong_id_bg = bg_design.replace(&bg_replace_color1, &(detection_prefix.to_string() + number_colors[n10])).replace(&bg_replace_color2, &(detection_prefix.to_string() + number_colors[n1]));
+    let mut bg = wrong_id_bg.clone();
+    let gradient_starts: Vec<(usize, &str)> = wrong_id_bg.match_indices("id=\"").collect();
+    for (index, _) in gradient_starts {
+        let start = index + 4;
+        let end = start + wrong_id_bg[start..].find("\"").expect("invalid svg gradient");
+        let id = &wrong_id_bg[start..end];
+        
+        bg = bg.replace(id, &xml_escape(&song.video_id));
+    }
+
+    let bleed_size = 100.0 + 2.0 * bleed;
+
+    svg.push(format!("<svg viewBox=\"{} {} {bleed_size} {bleed_size}\">", -bleed, -bleed));
+
+    svg.push(format!("<svg x=\"{}\" y=\"{}\" width=\"{bleed_size}\" height=\"{bleed_size}\" viewBox=\"0 0 100 100\" preserveAspectRatio=\"none\">", -bleed, -bleed));
+    svg.push(bg);
+    svg.push("</svg>".into());
+
+    svg.push(format!("<svg x=\"{}\" y=\"{}\">", year_x, year_y));
+    svg.push(year.path.to_string());
+    svg.push("</svg>".into());
+
+    svg.push(format!("<svg x=\"{}\" y=\"{}\">", artist_x, artist_y));
+    svg.push(artist);
+    svg.push("</svg>".into());
+
+    svg.push(format!("<svg x=\"{}\" y=\"{}\">", title_x, title_y));
+    svg.push(title);
+    svg.push("</svg>".into());
+
+    svg.push("<svg x=\"3\" y=\"3\" width=\"10\" height=\"10\" viewBox=\"0 0 100 100\">".into());
+    svg.push(icon.to_owned());
+    svg.push("</svg>".into());
+
+    svg.push("</svg>".into());
+
+    svg.iter().fold(String::new(), |a, b| a + b + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BUNDLED_DESIGN, BUNDLED_FONT, BUNDLED_ICON};
+
+    #[test]
+    fn card_text_is_escaped() {
+        let song = Song {
+            artist: "Tom & \"The\" Band's".into(),
+            title: "I <3 Rock > Roll".into(),
+            release_year: 1984,
+            video_id: "a&b".into(),
+            ..Default::default()
+        };
+        let font = Font::try_from_bytes(BUNDLED_FONT).unwrap();
+        let svg = create_card_preview(&song, &font, BUNDLED_ICON, BUNDLED_DESIGN);
+
+        let document = roxmltree::Document::parse(&svg).unwrap();
+        let texts: Vec<&str> = document.descendants().filter(|n| n.has_tag_name("text")).filter_map(|n| n.text()).collect();
+        assert!(texts.contains(&"Tom & \"The\" Band's"));
+        assert!(texts.contains(&"I <3 Rock > Roll"));
+    }
+}
@@ -0,0 +1,62 @@
+//! Combines rendered SVG pages into one printable PDF.
+//!
+//! Text is converted to outlines with the card font before embedding, so the PDF
+//! does not depend on any fonts installed on the printing machine.
+
+use std::{error::Error, path::Path};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref};
+use svg2pdf::usvg::{self, fontdb, PostProcessingSteps, TreeParsing, TreePostProc};
+
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+/// Writes `pages` in order as one PDF page each. `page_size` is width and height in mm.
+pub fn write_pdf(path: &Path, pages: &[String], font_data: Vec<u8>, page_size: (f32, f32)) -> Result<(), Box<dyn Error>> {
+
+    let mut fonts = fontdb::Database::new();
+    fonts.load_font_data(font_data);
+    let font_family = fonts.faces().next()
+        .and_then(|face| face.families.first())
+        .map(|(name, _)| name.clone())
+        .ok_or("Font file contains no usable font")?;
+
+    let options = usvg::Options { font_family, ..Default::default() };
+
+    let width = page_size.0 * POINTS_PER_MM;
+    let height = page_size.1 * POINTS_PER_MM;
+    let svg_name = Name(b"S1");
+
+    let mut next_ref = Ref::new(1);
+    let catalog_id = next_ref.bump();
+    let page_tree_id = next_ref.bump();
+    let page_ids: Vec<Ref> = pages.iter().map(|_| next_ref.bump()).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(pages.len() as i32);
+
+    for (page_id, svg) in page_ids.into_iter().zip(pages) {
+
+        let mut tree = usvg::Tree::from_str(svg, &options)?;
+        tree.postprocess(PostProcessingSteps::default(), &fonts);
+
+        let content_id = next_ref.bump();
+        let svg_id = next_ref.bump();
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, width, height));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().x_objects().pair(svg_name, svg_id);
+        page.finish();
+
+        next_ref = svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut pdf, svg_id);
+
+        let mut content = Content::new();
+        content.transform([width, 0.0, 0.0, height, 0.0, 0.0]).x_object(svg_name);
+        pdf.stream(content_id, &content.finish());
+    }
+
+    std::fs::write(path, pdf.finish())?;
+
+    Ok(())
+}
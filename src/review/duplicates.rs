@@ -0,0 +1,262 @@
+//! Songs that are in a list more than once, which would give the same card twice.
+//!
+//! Songs are duplicates if they link to the same video, or if their artists are similar and their
+//! titles are the same apart from version notes like "(Official Video)" or "- Remastered 2011".
+//! Remixes count as different songs.
+
+use std::collections::BTreeMap;
+use colored::Colorize;
+use log::*;
+
+use crate::{metadata::confidence::{normalize, similarity}, song_list::edit_log::{EditLog, SongFields}, sources::youtube::clean_title, Song};
+
+use super::input_num;
+
+/// Artists at least this similar can be the same
+const ARTIST_SIMILARITY: f32 = 0.85;
+/// Titles without version notes at least this similar can be the same
+const TITLE_SIMILARITY: f64 = 0.9;
+/// Title suffixes after " - " containing these words are version notes
+const VERSION_WORDS: [&str; 11] = ["remaster", "version", "mono", "stereo", "live", "lyric", "video", "audio", "single", "radio edit", "deluxe"];
+
+#[derive(Clone)]
+pub struct Duplicates {
+    /// Indices of the songs, in list order
+    pub songs: Vec<usize>,
+    /// Whether all songs link to the same video, otherwise only artist and title are alike
+    pub same_video: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum Choice {
+    /// Keep the song at the index, with the earliest release year of the group, and drop the others
+    Merge(usize),
+    /// Keep the song at the index as it is and drop the others
+    Drop(usize),
+    KeepAll,
+}
+
+/// Groups of songs that are probably the same, in list order.
+pub fn find_duplicates(songs: &[Song]) -> Vec<Duplicates> {
+
+    let keys: Vec<String> = songs.iter().map(|s| title_key(&s.title)).collect();
+
+    // songs alike to any song of a group join the group
+    let mut group_of: Vec<usize> = (0..songs.len()).collect();
+    for a in 0..songs.len() {
+        for b in a + 1..songs.len() {
+            if group_of[a] == group_of[b] || !is_duplicate(&songs[a], &songs[b], &keys[a], &keys[b]) {
+                continue;
+            }
+            let (from, to) = (group_of[b], group_of[a]);
+            for group in group_of.iter_mut().filter(|g| **g == from) {
+                *group = to;
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (index, group) in group_of.iter().enumerate() {
+        groups.entry(*group).or_default().push(index);
+    }
+
+    groups.into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| Duplicates {
+            same_video: !songs[group[0]].video_id.is_empty() && group.iter().all(|i| songs[*i].video_id == songs[group[0]].video_id),
+            songs: group,
+        })
+        .collect()
+}
+
+fn is_duplicate(a: &Song, b: &Song, a_key: &str, b_key: &str) -> bool {
+    if !a.video_id.is_empty() && a.video_id == b.video_id {
+        return true;
+    }
+    !a_key.is_empty() && strsim::normalized_levenshtein(a_key, b_key) >= TITLE_SIMILARITY && similarity(&a.artist, &b.artist) >= ARTIST_SIMILARITY
+}
+
+/// The title without version notes, normalized.
+fn title_key(title: &str) -> String {
+    let title = clean_title(title);
+    let title = match title.split_once(" - ") {
+        Some((main, suffix)) if VERSION_WORDS.iter().any(|w| suffix.to_lowercase().contains(w)) => main,
+        _ => &title,
+    };
+    normalize(title)
+}
+
+/// Applies the choices for the groups and removes the dropped songs, both as edits that can be undone.
+/// Returns the number of removed songs.
+pub fn apply_choices(songs: &mut Vec<Song>, log: &mut EditLog, choices: &[(Duplicates, Choice)]) -> usize {
+
+    let mut removed = Vec::new();
+
+    for (duplicates, choice) in choices {
+        let keep = match *choice {
+            Choice::KeepAll => continue,
+            Choice::Drop(keep) => keep,
+            Choice::Merge(keep) => {
+                merge(songs, log, keep, &duplicates.songs);
+                keep
+            },
+        };
+        removed.extend(duplicates.songs.iter().filter(|i| **i != keep));
+    }
+
+    removed.sort_unstable();
+    removed.dedup();
+    for index in removed.iter().rev() {
+        log.remove(songs, *index, "Drop duplicate");
+    }
+
+    removed.len()
+}
+
+/// Gives the song at `keep` the earliest release year of the group and the details the others add.
+fn merge(songs: &mut [Song], log: &mut EditLog, keep: usize, group: &[usize]) {
+
+    let Some(earliest) = group.iter().copied().min_by_key(|i| songs[*i].release_year) else {
+        return;
+    };
+    let earliest = songs[earliest].clone();
+    let others: Vec<Song> = group.iter().filter(|i| **i != keep).map(|i| songs[*i].clone()).collect();
+
+    let song = &mut songs[keep];
+    let before = SongFields::of(song);
+
+    if earliest.release_year < song.release_year {
+        song.release_year = earliest.release_year;
+        song.detected_title = earliest.detected_title;
+        song.year_justification = earliest.year_justification;
+        song.match_confidence = earliest.match_confidence;
+    }
+    for other in others {
+        if song.isrc.is_none() {
+            song.isrc = other.isrc;
+        }
+        for (key, value) in other.extra {
+            song.extra.entry(key).or_insert(value);
+        }
+    }
+
+    log.record(keep, "Merge duplicates", before, song);
+}
+
+/// Asks what to do with every group of duplicates and applies the choices.
+pub fn resolve_duplicates(songs: &mut Vec<Song>, log: &mut EditLog) {
+
+    let groups = find_duplicates(songs);
+    if groups.is_empty() {
+        return;
+    }
+
+    println!();
+    let count: usize = groups.iter().map(|g| g.songs.len()).sum();
+    info!("Found {} groups of duplicates with {} songs.", groups.len().to_string().yellow(), count.to_string().yellow());
+
+    let mut choices = Vec::new();
+
+    for (number, duplicates) in groups.iter().enumerate() {
+
+        println!();
+        println!("{}{}", format!("{}/{} ", number + 1, groups.len()).green(), if duplicates.same_video { "Same video" } else { "Similar songs" }.green());
+        for (position, index) in duplicates.songs.iter().enumerate() {
+            let song = &songs[*index];
+            println!("{} {} {} - {}", (position + 1).to_string().blue(), song.release_year.to_string().yellow(), song.artist.bright_green(), song.title.bright_green());
+            println!("  {}", format!("{} title: {}, {}", song.source.name(), song.raw_title, song.playback_url()).truecolor(100, 100, 100));
+        }
+        println!();
+        println!("Actions:");
+        println!("{} {}", "1".blue(), "Merge into one song with the earliest year".cyan());
+        println!("{} {}", "2".blue(), "Keep one song and drop the others".cyan());
+        println!("{} {}", "3".blue(), "Keep all".cyan());
+        println!("{} {}", "4".blue(), "Keep all remaining".cyan());
+        println!();
+
+        let choice = match input_num(1, 4) {
+            action @ (1 | 2) => {
+                println!("Which song to keep?");
+                let keep = duplicates.songs[input_num(1, duplicates.songs.len() as i32) as usize - 1];
+                if action == 1 { Choice::Merge(keep) } else { Choice::Drop(keep) }
+            },
+            3 => Choice::KeepAll,
+            _ => break,
+        };
+        choices.push((duplicates.clone(), choice));
+    }
+
+    let removed = apply_choices(songs, log, &choices);
+    info!("Removed {} duplicate songs", removed.to_string().green());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(artist: &str, title: &str, year: i32, video_id: &str) -> Song {
+        Song { artist: artist.into(), title: title.into(), release_year: year, video_id: video_id.into(), ..Default::default() }
+    }
+
+    fn groups(songs: &[Song]) -> Vec<(Vec<usize>, bool)> {
+        find_duplicates(songs).into_iter().map(|d| (d.songs, d.same_video)).collect()
+    }
+
+    #[test]
+    fn finds_same_videos_and_versions() {
+        let songs = [
+            song("Queen", "Bohemian Rhapsody", 1975, "a"),
+            song("ABBA", "Dancing Queen", 1976, "b"),
+            song("Queen", "Bohemian Rhapsody (Official Video)", 2008, "c"),
+            song("Various", "Something else", 2000, "b"),
+            song("Queen.", "Bohemian Rhapsody - Remastered 2011", 2011, "d"),
+            song("ABBA", "Dancing Queen (Remix)", 2020, "e"),
+            song("Queen", "Killer Queen", 1974, "f"),
+        ];
+        assert_eq!(groups(&songs), [(vec![0, 2, 4], false), (vec![1, 3], true)]);
+    }
+
+    #[test]
+    fn keeps_different_songs_apart() {
+        let songs = [
+            song("Queen", "Under Pressure", 1981, "a"),
+            song("Vanilla Ice", "Ice Ice Baby", 1990, "b"),
+            song("Queen", "Bohemian Rhapsody - Live", 1986, "c"),
+            song("Queen", "Radio Ga Ga", 1984, ""),
+            song("Queen", "Radio Ga Ga - Live Aid", 1985, ""),
+            song("Local", "Track", 2000, ""),
+            song("Other", "Song", 2001, ""),
+        ];
+        // "Live Aid" is a version note, songs without video ids are only alike by artist and title
+        assert_eq!(groups(&songs), [(vec![3, 4], false)]);
+    }
+
+    #[test]
+    fn merges_and_drops_as_edits() {
+        let mut songs = vec![
+            song("Queen", "Bohemian Rhapsody (Official Video)", 2008, "a"),
+            song("ABBA", "Dancing Queen", 1976, "b"),
+            song("Queen", "Bohemian Rhapsody", 1975, "c"),
+            song("ABBA", "Dancing Queen", 1976, "b"),
+        ];
+        songs[2].isrc = Some("GBUM71029604".into());
+        songs[2].extra.insert("Notes".into(), "Side A".into());
+        let mut log = EditLog::default();
+
+        let found = find_duplicates(&songs);
+        let choices = [(found[0].clone(), Choice::Merge(0)), (found[1].clone(), Choice::Drop(3))];
+        assert_eq!(apply_choices(&mut songs, &mut log, &choices), 2);
+
+        assert_eq!(songs.len(), 2);
+        assert_eq!((songs[0].title.as_str(), songs[0].release_year, songs[0].isrc.as_deref()), ("Bohemian Rhapsody (Official Video)", 1975, Some("GBUM71029604")));
+        assert_eq!(songs[0].extra.get("Notes").map(String::as_str), Some("Side A"));
+        assert_eq!(songs[1].artist, "ABBA");
+        assert_eq!(log.edits().iter().map(|e| e.action.as_str()).collect::<Vec<_>>(), ["Merge duplicates", "Drop duplicate", "Drop duplicate"]);
+
+        while log.undo(&mut songs).is_some() {}
+        assert_eq!(songs.iter().map(|s| (s.video_id.as_str(), s.release_year)).collect::<Vec<_>>(), [("a", 2008), ("b", 1976), ("c", 1975), ("b", 1976)]);
+        assert_eq!(songs[0].isrc, None);
+        assert!(songs[0].extra.is_empty());
+        assert_eq!(songs[2].isrc.as_deref(), Some("GBUM71029604"));
+    }
+}
@@ -0,0 +1,542 @@
+pub mod duplicates;
+pub mod tui;
+pub mod view;
+pub mod web;
+
+use std::{cmp::min, error::Error};
+use colored::Colorize;
+use log::*;
+use reqwest::Client;
+use text_io::read;
+
+use crate::{metadata::{confidence::DEFAULT_MIN_CONFIDENCE, LookupResult, MetadataLookup}, song_list::edit_log::{EditLog, SongFields}, Song};
+
+use self::{duplicates::{find_duplicates, resolve_duplicates}, view::{View, COMMAND_HELP}};
+
+/// Asks for the release year of every skipped song. `resolved` receives each song once it has one.
+pub async fn resolve_skipped(client: &Client, metadata: &MetadataLookup, skipped: Vec<Song>, mut resolved: impl FnMut(Song) -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+
+    println!();
+    println!();
+    info!("Revisiting songs that need manual intervention.");
+
+    let mut action_for_all = -1;
+
+    let mut current = 0;
+    let total_skipped = skipped.len() + 1;
+
+    for mut song in skipped {
+        loop {
+            if action_for_all == -1 {
+                println!();
+                current += 1;
+                println!("{}{}{}", current.to_string().green(), "/".green(), total_skipped.to_string().green());
+                println!();
+                println!("{:<16}{}", format!("{} title:", song.source.name()), song.raw_title.bright_green());
+                println!("Queried title:  {} - {}", song.artist.bright_green(), song.title.bright_green());
+                if let Some(detected_title) = &song.detected_title {
+                    println!("Best match:     {} ({}, {:.0}% confidence)", detected_title.yellow(), song.release_year.to_string().yellow(), song.match_confidence.unwrap_or(0.0) * 100.0);
+                }
+                println!();
+                println!("Actions:");
+                println!("{} {}{}{}", "1".blue(), format!("Use {} (", song.source.year_description()).cyan(), song.youtube_year.to_string().blue(), ")".cyan());
+                println!("{} {}", "2".blue(), "Manually set release year".cyan());
+                println!("{} {}", "3".blue(), "Edit song name for database query".cyan());
+                println!("{} {}", "4".blue(), format!("Use {} for all remaining", song.source.year_description()).cyan());
+                println!("{} {}", "5".blue(), "Manually set release year for all remaining".cyan());
+                if song.detected_title.is_some() {
+                    println!("{} {}", "6".blue(), "Accept best match".cyan());
+                }
+                println!();
+                println!("Enter number:");
+            }
+            let mut input = 0;
+            if action_for_all == -1 {
+                input = input_num(1, if song.detected_title.is_some() { 6 } else { 5 });
+                if input == 4 {action_for_all = 1}
+                if input == 5 {action_for_all = 2}
+            }
+            if action_for_all != -1 {
+                input = action_for_all;
+            }
+
+            match input {
+                1 => {
+                    song.release_year = song.youtube_year;
+                    song.year_justification = Some(song.source.year_description().into());
+                },
+                2 => {
+                    println!("Enter year for {}:", song.raw_title.bright_green());
+                    song.release_year = input_num(i32::MIN, i32::MAX);
+                    song.year_justification = Some("Set manually".into());
+                },
+                3 => {
+                    match custom_query(client, metadata, &mut song).await {
+                        Ok(_) => (),
+                        Err(_) => continue,
+                    }
+                },
+                6 => (),
+                _ => return Err("unknown input".into()),
+            }
+            info!("Using {} for {}", song.release_year.to_string().green(), song.raw_title.cyan());
+            break;
+        }
+        resolved(song)?;
+    }
+
+    info!("All dates specified. Continuing with final rewiew...");
+
+    Ok(())
+}
+
+/// Lets the user check and fix the songs page by page. Every change is recorded in `log`.
+pub async fn review_songs(client: &Client, metadata: &MetadataLookup, songs: &mut Vec<Song>, log: &mut EditLog) -> Result<(), Box<dyn Error>> {
+
+    let mut page = 0;
+    let mut elements_per_page = 20;
+    let mut view = View::default();
+    let mut shown: Vec<usize> = (0..songs.len()).collect();
+    // comparing all songs takes a while on large lists, so this is only redone after changes
+    let mut duplicates = None;
+    'outer: loop {
+        let page_count = (f32::ceil(shown.len() as f32 / elements_per_page as f32) as u32).max(1);
+        page = page.min(page_count - 1);
+
+        let page_str = format!("Page {}/{}", page + 1, page_count);
+        println!();
+        if view.is_empty() {
+            println!("{}", page_str.green());
+        } else {
+            println!("{} {}", page_str.green(), format!("({} of {} songs: {})", shown.len(), songs.len(), view.describe()).yellow());
+        }
+        let elements_displayed = draw_table(songs, &shown, page, elements_per_page);
+        println!();
+        println!("Actions:");
+        println!("{}", "Number to select element".cyan());
+        println!("{}", "a/d to change page".cyan());
+        println!("{}", "+/- to change number of elements per page".cyan());
+        println!("{} {}", "Filter and sort:".cyan(), COMMAND_HELP.cyan());
+        if songs.iter().any(|s| s.isrc.is_some()) {
+            println!("{}", "i to look up all songs with an ISRC".cyan());
+        }
+        let groups = duplicates.get_or_insert_with(|| find_duplicates(songs));
+        if !groups.is_empty() {
+            let count: usize = groups.iter().map(|g| g.songs.len()).sum();
+            println!("{}", format!("m to merge or drop duplicates ({} groups of duplicates with {} songs)", groups.len(), count).cyan());
+        }
+        if log.can_undo() {
+            println!("{}", "u to undo the last change".cyan());
+        }
+        if log.can_redo() {
+            println!("{}", "r to redo the undone change".cyan());
+        }
+        println!("{}", "y to finish".cyan());
+        println!();
+        print_input_arrow();
+        let input: String = read!("{}\n");
+        match input.parse::<u32>() {
+            Ok(num) => {
+                loop {
+                    if num > min(elements_displayed, elements_per_page) || num < 1 {
+                        continue 'outer;
+                    }
+                    let index = shown[((num - 1) + (page * elements_per_page)) as usize];
+                    let selected = songs.get_mut(index).unwrap();
+                    println!("Selected:");
+                    println!("Title for card:  {} - {}", selected.artist.bright_green(), selected.title.bright_green());
+                    if let Some(title) = &selected.detected_title {
+                        println!("Detected title:  {}", title.bright_green());
+                    }
+                    println!("Year:            {}", selected.release_year.to_string().bright_green());
+                    if let Some(justification) = &selected.year_justification {
+                        println!("Year source:     {}", justification.bright_green());
+                    }
+                    println!();
+                    println!("Actions:");
+                    println!("{} {}", "1".blue(), "New query".cyan());
+                    println!("{} {}", "2".blue(), "Change artist".cyan());
+                    println!("{} {}", "3".blue(), "Change title".cyan());
+                    println!("{} {}", "4".blue(), "Change year".cyan());
+                    println!("{} {}{}{}", "5".blue(), format!("Switch to {} (", selected.source.year_description()).cyan(), selected.youtube_year.to_string().blue(), ")".cyan());
+                    println!("{} {}", "6".blue(), "Back".cyan());
+                    println!();
+                    let action = input_num(1, 6);
+                    let before = SongFields::of(selected);
+                    match action {
+                        1 => {
+                            match custom_query(client, metadata, selected).await {
+                                Ok(_) => (),
+                                Err(_) => continue,
+                            }
+                        },
+                        2 => {
+                            println!("    {}", selected.artist.blue());
+                            print_input_arrow();
+                            selected.artist = read!("{}\n");
+                        },
+                        3 => {
+                            println!("    {}", selected.title.blue());
+                            print_input_arrow();
+                            selected.title = read!("{}\n");
+                        },
+                        4 => {
+                            println!("    {}", selected.release_year.to_string().blue());
+                            selected.release_year = input_num(i32::MIN, i32::MAX);
+                            selected.year_justification = Some("Set manually".into());
+                        },
+                        5 => {
+                            selected.release_year = selected.youtube_year;
+                            selected.year_justification = Some(selected.source.year_description().into());
+                            println!("Using {} for {}", selected.release_year.to_string().blue(), selected.raw_title.green());
+                        },
+                        6 => continue 'outer,
+                        _ => return Err("unknown input".into()),
+                    }
+                    log.record(index, ["New query", "Change artist", "Change title", "Change year", "Switch to source year"][action as usize - 1], before, selected);
+                    duplicates = None;
+                }
+            },
+            Err(_) => {
+                match input.as_str() {
+                    "a" => {
+                        page = page.saturating_sub(1);
+                    },
+                    "d" => {
+                        if page < page_count - 1 {
+                            page += 1;
+                        }
+                    },
+                    "+" => {
+                        elements_per_page += 10;
+                    },
+                    "-" => {
+                        if elements_per_page > 10 {
+                            elements_per_page -= 10;
+                        }
+                    },
+                    "i" => {
+                        lookup_isrcs(client, metadata, songs, log).await;
+                        duplicates = None;
+                    },
+                    "m" => {
+                        resolve_duplicates(songs, log);
+                        shown = view.indices(songs);
+                        duplicates = None;
+                    },
+                    "u" => {
+                        match log.undo(songs) {
+                            Some(edit) => info!("Undid {}", edit.describe().cyan()),
+                            None => info!("Nothing to undo"),
+                        }
+                        shown = view.indices(songs);
+                        duplicates = None;
+                    },
+                    "r" => {
+                        match log.redo(songs) {
+                            Some(edit) => info!("Redid {}", edit.describe().cyan()),
+                            None => info!("Nothing to redo"),
+                        }
+                        shown = view.indices(songs);
+                        duplicates = None;
+                    },
+                    "y" => break,
+                    "" => continue,
+                    command => match view.apply(command) {
+                        Ok(()) => {
+                            shown = view.indices(songs);
+                            page = 0;
+                        },
+                        Err(e) => println!("{}", e.to_string().red()),
+                    },
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the year of every song with an ISRC that MusicBrainz knows by the exact match.
+/// Returns the number of updated songs.
+async fn lookup_isrcs(client: &Client, metadata: &MetadataLookup, songs: &mut [Song], log: &mut EditLog) -> usize {
+
+    let mut updated = 0;
+
+    for (index, song) in songs.iter_mut().enumerate() {
+        let Some(isrc) = &song.isrc else {
+            continue;
+        };
+        let results = match metadata.lookup_isrc(client, isrc).await {
+            Ok(results) => results,
+            Err(e) => {
+                warn!("ISRC lookup for {} - {} failed: {}", song.artist, song.title, e);
+                continue;
+            }
+        };
+        let Some(result) = results.first() else {
+            warn!("No recording with ISRC {} ({} - {})", isrc, song.artist, song.title);
+            continue;
+        };
+        let before = SongFields::of(song);
+        song.release_year = result.year;
+        song.detected_title = Some(result.detected_title.clone());
+        song.year_justification = Some(result.justification.clone());
+        song.match_confidence = Some(1.0);
+        log.record(index, "ISRC lookup", before, song);
+        updated += 1;
+    }
+
+    info!("Updated {} songs by ISRC", updated.to_string().green());
+    updated
+}
+
+async fn custom_query(client: &Client, metadata: &MetadataLookup, song: &mut Song) -> Result<(), Box<dyn Error>> {
+    println!("Artist:");
+    print_input_arrow();
+    let custom_query_artist: String = read!("{}\n");
+    println!("Title:");
+    print_input_arrow();
+    let custom_query_title: String = read!("{}\n");
+    match metadata.lookup(client, &custom_query_artist, &custom_query_title, None).await {
+        Ok(results) => {
+            println!();
+            for (index, result) in results.iter().enumerate() {
+                let d = match &result.disambiguation {Some(d) => d, None => ""};
+                println!("{} {}", (index + 1).to_string().blue(), (result.year.to_string() + ": " + &result.detected_title + "; " + d).cyan());
+                println!("  {}", result.justification.truecolor(100, 100, 100));
+            }
+            println!("{} {}", (results.len() + 1).to_string().blue(), "Back".cyan());
+            println!();
+            let input = input_num(1, results.len() as i32 + 1);
+            if input == results.len() as i32 + 1 {
+                return Err("Back".into())
+            }
+            use_picked_result(song, &results[input as usize - 1]);
+        },
+        Err(_) => {
+            info!("{}", "Song not found".red());
+            return Err("Song not found".into());
+        }
+    }
+    Ok(())
+}
+
+/// Takes the year of a lookup result the user picked for `song`.
+fn use_picked_result(song: &mut Song, result: &LookupResult) {
+    song.release_year = result.year;
+    song.detected_title = Some(result.detected_title.clone());
+    song.year_justification = Some(result.justification.clone());
+    // picked by hand, so the confidence of the original match no longer applies
+    song.match_confidence = None;
+}
+
+/// Draws a page of the songs at the `shown` indices.
+fn draw_table(songs: &[Song], shown: &[usize], page: u32, elements_per_page: u32) -> u32 {
+
+    let elements: Vec<&Song> = shown.iter().map(|i| &songs[*i]).collect();
+
+    let longest_artist = elements.iter().map(|s| s.artist.len()).max().unwrap_or(0) as u32;
+    let longest_title = elements.iter().map(|s| s.title.len()).max().unwrap_or(0) as u32;
+    let longest_detected_title = elements.iter().map(|s| s.detected_title.clone().unwrap_or("".to_string()).len()).max().unwrap_or(0) as u32;
+    let longest_year = elements.iter().map(|s| s.release_year.to_string().len()).max().unwrap_or(0) as u32;
+
+    let mut displayed_songs: Vec<Option<&Song>> = Vec::new();
+    for i in 0..elements_per_page {
+        displayed_songs.push(elements.get((i + (page * elements_per_page)) as usize).copied());
+    }
+
+    let displayed_songs_count = displayed_songs.iter().filter(|s| s.is_some()).count() as u32;
+
+    const TABLE_R: u8 = 100;
+    const TABLE_G: u8 = TABLE_R;
+    const TABLE_B: u8 = TABLE_R;
+    const CONFIDENCE_WIDTH: u32 = 5;
+
+    print!("{}", "┌────┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_artist + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_title + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_detected_title + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_year + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┬".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..CONFIDENCE_WIDTH + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    println!("{}", "┐".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    print!("{}", "│ ## │ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    print!("{}", "Artist".to_string().green());
+    fillup_spaces("Artist".to_string(), longest_artist + 1);
+    print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    print!("{}", "Title".to_string().green());
+    fillup_spaces("Title".to_string(), longest_title + 1);
+    print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    print!("{}", "Detected Title".to_string().green());
+    fillup_spaces("Detected Title".to_string(), longest_detected_title + 1);
+    print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    print!("{}", "Year".to_string().green());
+    fillup_spaces("Year".to_string(), longest_year + 1);
+    print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    print!("{}", "Match".to_string().green());
+    fillup_spaces("Match".to_string(), CONFIDENCE_WIDTH + 1);
+    println!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+
+    print!("{}", "├────┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_artist + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_title + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_detected_title + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_year + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┼".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..CONFIDENCE_WIDTH + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    println!("{}", "┤".truecolor(TABLE_R, TABLE_G, TABLE_B));
+
+    for (num, song) in (1..).zip(displayed_songs) {
+        
+        let artist;
+        let title;
+        let detected_title;
+        let year;
+        let base_detected_title;
+        match song {
+            Some(song) => {
+                artist = song.artist.clone();
+                title = song.title.clone();
+
+                detected_title = match song.detected_title.clone() {
+                    Some(mut raw_detected_title) => {
+
+                        base_detected_title = raw_detected_title.clone();
+                
+                        let detected_title_copy = raw_detected_title.clone();
+                        // titles from CSV imports don't have to be "artist - title", those are shown as they are
+                        match detected_title_copy.split_once(" - ") {
+                            Some((artist_split, title_split)) => {
+                                let artists: Vec<&str> = artist_split.split(", ").collect();
+
+                                for det_artist in artists {
+                                    if let Some((left, right)) = raw_detected_title.split_once(det_artist)
+                                        && artist.to_lowercase().contains(det_artist.to_lowercase().as_str()) {
+                                            raw_detected_title = left.to_string() + &det_artist.green().to_string() + right;
+                                        }
+                                }
+                                if let Some((left, right)) = raw_detected_title.split_once(title_split)
+                                    && title.to_lowercase().contains(title_split.to_lowercase().as_str()) {
+                                        raw_detected_title = left.to_string() + &title_split.green().to_string() + right;
+                                    }
+
+                                raw_detected_title.blue().to_string()
+                            },
+                            None => raw_detected_title,
+                        }
+                    },
+                    None => {
+                        base_detected_title = String::new();
+                        String::new()
+                    }
+                };
+
+                year = song.release_year.to_string();
+            },
+            None => {
+                artist = String::new();
+                title = String::new();
+                detected_title = String::new();
+                year = String::new();
+                base_detected_title = String::new();
+            },
+        };
+        let num_str = (if num <= 9 {" ".to_string()} else {"".to_string()}) + &num.to_string();
+        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+        print!("{}{}", num_str.blue(), " │ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+        print!("{}", artist.green());
+        fillup_spaces(artist, longest_artist + 1);
+        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+        print!("{}", title.green());
+        fillup_spaces(title, longest_title + 1);
+        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+        print!("{}", detected_title.green());
+        fillup_spaces(base_detected_title, longest_detected_title + 1);
+        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+        print!("{}", year.green());
+        fillup_spaces(year, longest_year + 1);
+        print!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+        let confidence = song.and_then(|s| s.match_confidence);
+        let confidence_str = confidence.map(|c| format!("{:.0}%", c * 100.0)).unwrap_or_default();
+        print!("{}", match confidence {
+            Some(c) if c >= 0.8 => confidence_str.green(),
+            Some(c) if c >= DEFAULT_MIN_CONFIDENCE => confidence_str.yellow(),
+            _ => confidence_str.red(),
+        });
+        fillup_spaces(confidence_str, CONFIDENCE_WIDTH + 1);
+        println!("{}", "│ ".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+
+    print!("{}", "└────┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_artist + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_title + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_detected_title + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..longest_year + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    print!("{}", "┴".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    for _ in 0..CONFIDENCE_WIDTH + 2 {
+        print!("{}", "─".truecolor(TABLE_R, TABLE_G, TABLE_B));
+    }
+    println!("{}", "┘".truecolor(TABLE_R, TABLE_G, TABLE_B));
+
+    displayed_songs_count
+
+}
+
+fn fillup_spaces(string: String, length: u32) {
+    for _ in (string.chars().count() as u32)..length {
+        print!(" ");
+    }
+}
+
+pub fn print_input_arrow() {
+    print!("{}", "==> ".green());
+}
+
+pub fn input_num(range_min: i32, range_max: i32) -> i32 {
+    loop {
+        print_input_arrow();
+        let raw_input: String = read!("{}\n");
+        if let Ok(i) = raw_input.parse::<i32>()
+            && i >= range_min && i <= range_max {
+                return i;
+            };
+    }
+}
@@ -0,0 +1,566 @@
+//! Full-screen review of a song list, the alternative to the prompts of [`super::review_songs`].
+//!
+//! Songs are navigated with the arrow keys and edited in place, lookups run without leaving the screen.
+
+use std::error::Error;
+use log::LevelFilter;
+use ratatui::{crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}, layout::{Constraint, Flex, Layout, Rect}, style::{Color, Style, Stylize}, text::{Line, Span, Text}, widgets::{Block, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState}, DefaultTerminal, Frame};
+use reqwest::Client;
+
+use crate::{metadata::{confidence::DEFAULT_MIN_CONFIDENCE, LookupResult, MetadataLookup}, song_list::edit_log::{EditLog, SongFields}, Song};
+
+use super::{duplicates::{apply_choices, find_duplicates, Choice, Duplicates}, lookup_isrcs, use_picked_result, view::{View, COMMAND_HELP}};
+
+/// Columns that can be edited in place
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Artist,
+    Title,
+    Year,
+}
+
+impl Field {
+
+    const ALL: [Field; 3] = [Field::Artist, Field::Title, Field::Year];
+
+    /// Index of the field's column in the table
+    fn column(self) -> usize {
+        match self {
+            Field::Artist => 1,
+            Field::Title => 2,
+            Field::Year => 4,
+        }
+    }
+
+    /// Action name in the edit log
+    fn action(self) -> &'static str {
+        match self {
+            Field::Artist => "Change artist",
+            Field::Title => "Change title",
+            Field::Year => "Change year",
+        }
+    }
+
+    fn value(self, song: &Song) -> String {
+        match self {
+            Field::Artist => song.artist.clone(),
+            Field::Title => song.title.clone(),
+            Field::Year => song.release_year.to_string(),
+        }
+    }
+}
+
+/// Single-line text input, the cursor counts characters.
+struct Input {
+    text: String,
+    cursor: usize,
+}
+
+impl Input {
+
+    fn new(text: String) -> Self {
+        let cursor = text.chars().count();
+        Input { text, cursor }
+    }
+
+    fn byte_index(&self) -> usize {
+        self.text.char_indices().nth(self.cursor).map_or(self.text.len(), |(i, _)| i)
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let length = self.text.chars().count();
+        match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let index = self.byte_index();
+                self.text.insert(index, c);
+                self.cursor += 1;
+            },
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let index = self.byte_index();
+                self.text.remove(index);
+            },
+            KeyCode::Delete if self.cursor < length => {
+                let index = self.byte_index();
+                self.text.remove(index);
+            },
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(length),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = length,
+            _ => (),
+        }
+    }
+
+    /// The text with a bar at the cursor
+    fn with_cursor(&self) -> String {
+        let index = self.byte_index();
+        format!("{}▏{}", &self.text[..index], &self.text[index..])
+    }
+}
+
+enum Mode {
+    Browse,
+    Edit { field: Field, input: Input },
+    /// Typing "Artist - Title" for a new lookup
+    Query { input: Input },
+    /// Picking one of the results of a new lookup
+    Results { results: Vec<LookupResult>, list: ListState },
+    /// Typing a filter or sort command, see [`super::view`]
+    Command { input: Input },
+    /// Choosing what to do with each group of duplicates, applied after the last group
+    Duplicates { groups: Vec<Duplicates>, group: usize, list: ListState, choices: Vec<(Duplicates, Choice)> },
+}
+
+/// Work that needs the event loop, as it awaits requests
+enum Action {
+    Quit,
+    Lookup { artist: String, title: String },
+    LookupIsrcs,
+}
+
+struct ReviewScreen<'a> {
+    songs: &'a mut Vec<Song>,
+    log: &'a mut EditLog,
+    view: View,
+    /// Indices of the songs in the table
+    shown: Vec<usize>,
+    table: TableState,
+    field: Field,
+    mode: Mode,
+    /// Replaces the key hints until the next key press
+    status: Option<String>,
+    /// Rows visible in the table, for page-wise scrolling
+    page_size: usize,
+}
+
+/// Like [`super::review_songs`], every change is recorded in `log`.
+pub async fn review_songs_tui(client: &Client, metadata: &MetadataLookup, songs: &mut Vec<Song>, log: &mut EditLog) -> Result<(), Box<dyn Error>> {
+
+    if songs.is_empty() {
+        return Ok(());
+    }
+
+    let mut terminal = ratatui::try_init()?;
+
+    // log lines would be drawn over the screen, lookup errors are shown in the status bar instead
+    let log_level = log::max_level();
+    log::set_max_level(LevelFilter::Off);
+
+    let result = ReviewScreen::new(songs, log).run(&mut terminal, client, metadata).await;
+    ratatui::restore();
+
+    log::set_max_level(log_level);
+    result
+}
+
+impl<'a> ReviewScreen<'a> {
+
+    fn new(songs: &'a mut Vec<Song>, log: &'a mut EditLog) -> Self {
+        let shown = (0..songs.len()).collect();
+        ReviewScreen { songs, log, view: View::default(), shown, table: TableState::default().with_selected(0), field: Field::Artist, mode: Mode::Browse, status: None, page_size: 20 }
+    }
+
+    async fn run(mut self, terminal: &mut DefaultTerminal, client: &Client, metadata: &MetadataLookup) -> Result<(), Box<dyn Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match self.handle_key(key) {
+                None => (),
+                Some(Action::Quit) => return Ok(()),
+                Some(Action::Lookup { artist, title }) => {
+                    self.status = Some(format!("Searching {} - {}...", artist, title));
+                    terminal.draw(|frame| self.draw(frame))?;
+                    match metadata.lookup(client, &artist, &title, None).await {
+                        Ok(results) => {
+                            self.status = None;
+                            self.mode = Mode::Results { results, list: ListState::default().with_selected(Some(0)) };
+                        },
+                        Err(e) => self.status = Some(e.to_string()),
+                    }
+                },
+                Some(Action::LookupIsrcs) => {
+                    self.status = Some("Looking up songs by ISRC...".into());
+                    terminal.draw(|frame| self.draw(frame))?;
+                    let updated = lookup_isrcs(client, metadata, self.songs, self.log).await;
+                    self.status = Some(format!("Updated {} songs by ISRC", updated));
+                },
+            }
+        }
+    }
+
+    /// Row of the selected song in the table
+    fn selected(&self) -> usize {
+        self.table.selected().unwrap_or(0).min(self.shown.len().saturating_sub(1))
+    }
+
+    fn select(&mut self, row: isize) {
+        self.table.select(Some(row.clamp(0, (self.shown.len() as isize - 1).max(0)) as usize));
+    }
+
+    /// Index of the selected song, `None` if the filters match no song
+    fn current(&self) -> Option<usize> {
+        self.shown.get(self.selected()).copied()
+    }
+
+    /// Shows the songs matching the changed view, keeping the selected song selected if it still matches.
+    fn update_shown(&mut self) {
+        let current = self.current();
+        self.shown = self.view.indices(self.songs);
+        let row = current.and_then(|c| self.shown.iter().position(|i| *i == c)).unwrap_or(0);
+        self.select(row as isize);
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+
+        self.status = None;
+        let selected = self.selected();
+        let current = self.current();
+
+        match (std::mem::replace(&mut self.mode, Mode::Browse), current) {
+            (Mode::Browse, _) => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => self.select(selected as isize - 1),
+                KeyCode::Down | KeyCode::Char('j') => self.select(selected as isize + 1),
+                KeyCode::PageUp => self.select(selected as isize - self.page_size as isize),
+                KeyCode::PageDown => self.select(selected as isize + self.page_size as isize),
+                KeyCode::Home | KeyCode::Char('g') => self.select(0),
+                KeyCode::End | KeyCode::Char('G') => self.select(isize::MAX),
+                KeyCode::Left | KeyCode::BackTab => {
+                    let index = Field::ALL.iter().position(|f| *f == self.field).unwrap();
+                    self.field = Field::ALL[(index + Field::ALL.len() - 1) % Field::ALL.len()];
+                },
+                KeyCode::Right | KeyCode::Tab => {
+                    let index = Field::ALL.iter().position(|f| *f == self.field).unwrap();
+                    self.field = Field::ALL[(index + 1) % Field::ALL.len()];
+                },
+                KeyCode::Enter | KeyCode::Char('e') => self.start_edit(self.field),
+                KeyCode::Char('a') => self.start_edit(Field::Artist),
+                KeyCode::Char('t') => self.start_edit(Field::Title),
+                KeyCode::Char('y') => self.start_edit(Field::Year),
+                KeyCode::Char('n') => if let Some(current) = current {
+                    let song = &self.songs[current];
+                    self.mode = Mode::Query { input: Input::new(format!("{} - {}", song.artist, song.title)) };
+                },
+                KeyCode::Char('s') => if let Some(current) = current {
+                    let song = &mut self.songs[current];
+                    let before = SongFields::of(song);
+                    song.release_year = song.youtube_year;
+                    song.year_justification = Some(song.source.year_description().into());
+                    self.log.record(current, "Switch to source year", before, song);
+                    self.status = Some(format!("Using the {} {} for {}", song.source.year_description(), song.release_year, song.raw_title));
+                },
+                KeyCode::Char('i') => return Some(Action::LookupIsrcs),
+                KeyCode::Char('m') => {
+                    let groups = find_duplicates(self.songs);
+                    match groups.is_empty() {
+                        true => self.status = Some("No duplicates found".into()),
+                        false => self.mode = Mode::Duplicates { groups, group: 0, list: ListState::default().with_selected(Some(0)), choices: Vec::new() },
+                    }
+                },
+                KeyCode::Char('u') => {
+                    let edit = self.log.undo(self.songs).map(|edit| (edit.song, format!("Undid {}", edit.describe())));
+                    self.show_edit(edit, "Nothing to undo");
+                },
+                KeyCode::Char('r') => {
+                    let edit = self.log.redo(self.songs).map(|edit| (edit.song, format!("Redid {}", edit.describe())));
+                    self.show_edit(edit, "Nothing to redo");
+                },
+                KeyCode::Char('/') => self.mode = Mode::Command { input: Input::new("/".into()) },
+                KeyCode::Char(':') | KeyCode::Char('f') => self.mode = Mode::Command { input: Input::new(String::new()) },
+                KeyCode::Esc if !self.view.is_empty() => {
+                    self.view = View::default();
+                    self.update_shown();
+                },
+                KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+                _ => (),
+            },
+            (Mode::Command { mut input }, _) => match key.code {
+                KeyCode::Esc => (),
+                KeyCode::Enter => match self.view.apply(&input.text) {
+                    Ok(()) => self.update_shown(),
+                    Err(e) => {
+                        self.status = Some(e.to_string());
+                        self.mode = Mode::Command { input };
+                    },
+                },
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Command { input };
+                },
+            },
+            (Mode::Duplicates { groups, group, mut list, mut choices }, _) => {
+                let selected_song = groups[group].songs[list.selected().unwrap_or(0).min(groups[group].songs.len() - 1)];
+                let choice = match key.code {
+                    KeyCode::Enter | KeyCode::Char('m') => Some(Choice::Merge(selected_song)),
+                    KeyCode::Char('d') => Some(Choice::Drop(selected_song)),
+                    KeyCode::Char('k') => Some(Choice::KeepAll),
+                    _ => None,
+                };
+                if let Some(choice) = choice {
+                    choices.push((groups[group].clone(), choice));
+                }
+                let group = group + choice.is_some() as usize;
+                if key.code == KeyCode::Esc || group == groups.len() {
+                    let removed = apply_choices(self.songs, self.log, &choices);
+                    self.update_shown();
+                    self.status = Some(format!("Removed {} duplicate songs", removed));
+                } else {
+                    // k keeps all, so only the arrow keys move
+                    match key.code {
+                        _ if choice.is_some() => list.select(Some(0)),
+                        KeyCode::Up => list.select_previous(),
+                        KeyCode::Down => list.select_next(),
+                        _ => (),
+                    }
+                    self.mode = Mode::Duplicates { groups, group, list, choices };
+                }
+            },
+            // the other modes are only entered with a song selected
+            (_, None) => (),
+            (Mode::Edit { field, mut input }, Some(current)) => match key.code {
+                KeyCode::Esc => (),
+                KeyCode::Enter => {
+                    let text = input.text.trim().to_string();
+                    let song = &mut self.songs[current];
+                    let before = SongFields::of(song);
+                    match field {
+                        Field::Artist => song.artist = text,
+                        Field::Title => song.title = text,
+                        Field::Year => match text.parse::<i32>() {
+                            Ok(year) => {
+                                song.release_year = year;
+                                song.year_justification = Some("Set manually".into());
+                            },
+                            Err(_) => {
+                                self.status = Some("The year has to be a number".into());
+                                self.mode = Mode::Edit { field, input };
+                            },
+                        },
+                    }
+                    self.log.record(current, field.action(), before, song);
+                },
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Edit { field, input };
+                },
+            },
+            (Mode::Query { mut input }, Some(_)) => match key.code {
+                KeyCode::Esc => (),
+                KeyCode::Enter => match input.text.split_once(" - ") {
+                    Some((artist, title)) => return Some(Action::Lookup { artist: artist.trim().to_string(), title: title.trim().to_string() }),
+                    None => {
+                        self.status = Some("Enter the query as Artist - Title".into());
+                        self.mode = Mode::Query { input };
+                    },
+                },
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Query { input };
+                },
+            },
+            (Mode::Results { results, mut list }, Some(current)) => match key.code {
+                KeyCode::Esc => (),
+                KeyCode::Enter => {
+                    if let Some(result) = list.selected().and_then(|i| results.get(i)) {
+                        let song = &mut self.songs[current];
+                        let before = SongFields::of(song);
+                        use_picked_result(song, result);
+                        self.log.record(current, "New query", before, song);
+                        self.status = Some(format!("Using {} for {}", song.release_year, song.raw_title));
+                    }
+                },
+                _ => {
+                    match key.code {
+                        KeyCode::Up | KeyCode::Char('k') => list.select_previous(),
+                        KeyCode::Down | KeyCode::Char('j') => list.select_next(),
+                        _ => (),
+                    }
+                    self.mode = Mode::Results { results, list };
+                },
+            },
+        }
+
+        None
+    }
+
+    /// Selects the song of an undone or redone edit and shows what happened.
+    fn show_edit(&mut self, edit: Option<(usize, String)>, nothing: &str) {
+        match edit {
+            Some((song, description)) => {
+                // undoing and redoing can put back and remove songs
+                self.update_shown();
+                if let Some(row) = self.shown.iter().position(|i| *i == song) {
+                    self.select(row as isize);
+                }
+                self.status = Some(description);
+            },
+            None => self.status = Some(nothing.into()),
+        }
+    }
+
+    fn start_edit(&mut self, field: Field) {
+        self.field = field;
+        if let Some(current) = self.current() {
+            self.mode = Mode::Edit { field, input: Input::new(field.value(&self.songs[current])) };
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+
+        let [header_area, table_area, details_area, status_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(5),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+
+        let current = self.current();
+
+        let header = match self.view.is_empty() {
+            true => format!(" Reviewing {} songs", self.songs.len()),
+            false => format!(" Showing {} of {} songs: {}", self.shown.len(), self.songs.len(), self.view.describe()),
+        };
+        frame.render_widget(Line::from(vec![" Carnister ".black().on_green().bold(), header.into()]), header_area);
+
+        // borders and the header row
+        self.page_size = (table_area.height as usize).saturating_sub(3).max(1);
+
+        let rows: Vec<Row> = self.shown.iter().map(|&index| {
+            let song = &self.songs[index];
+            let mut cells = vec![
+                Cell::from((index + 1).to_string()).dark_gray(),
+                Cell::from(song.artist.clone()),
+                Cell::from(song.title.clone()),
+                Cell::from(song.detected_title.clone().unwrap_or_default()).blue(),
+                Cell::from(song.release_year.to_string()),
+                Cell::from(song.youtube_year.to_string()).dark_gray(),
+                confidence_cell(song.match_confidence),
+            ];
+            if let Mode::Edit { field, input } = &self.mode && Some(index) == current {
+                cells[field.column()] = Cell::from(input.with_cursor()).yellow();
+            }
+            Row::new(cells)
+        }).collect();
+
+        let number_width = self.songs.len().to_string().len() as u16;
+        let table = Table::new(rows, [
+            Constraint::Length(number_width),
+            Constraint::Fill(3),
+            Constraint::Fill(3),
+            Constraint::Fill(4),
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Length(6),
+        ])
+            .header(Row::new(["#", "Artist", "Title", "Detected title", "Year", "Source", "Match"]).green().bold())
+            .block(Block::bordered())
+            .row_highlight_style(Style::new().bg(Color::Rgb(50, 50, 50)))
+            .cell_highlight_style(Style::new().reversed())
+            .highlight_symbol("▶ ");
+
+        self.table.select_column(Some(self.field.column()));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let details = match current {
+            Some(current) => {
+                let song = &self.songs[current];
+                Paragraph::new(vec![
+                    Line::from(vec![format!("{} title: ", song.source.name()).dark_gray(), song.raw_title.clone().into()]),
+                    Line::from(vec!["Year source: ".dark_gray(), song.year_justification.clone().unwrap_or_default().into()]),
+                    Line::from(vec!["Link: ".dark_gray(), song.playback_url().into()]),
+                ]).block(Block::bordered().title(format!(" Song {}/{} ", current + 1, self.songs.len())))
+            },
+            None => Paragraph::new("No song matches the filters, Esc shows all songs.".yellow()).block(Block::bordered()),
+        };
+        frame.render_widget(details, details_area);
+
+        match &mut self.mode {
+            Mode::Results { results, list } => draw_results(frame, results, list),
+            Mode::Duplicates { groups, group, list, .. } => draw_duplicates(frame, self.songs, &groups[*group], *group, groups.len(), list),
+            _ => (),
+        }
+
+        let status = match (&self.status, &self.mode) {
+            (Some(status), _) => Line::from(status.clone().yellow()),
+            (None, Mode::Browse) => key_hints(&[("↑↓", "move"), ("←→", "column"), ("Enter", "edit"), ("n", "new query"), ("s", "use source year"), ("i", "ISRC lookup"), ("u/r", "undo/redo"), ("m", "duplicates"), ("/", "search"), ("f", "filter/sort"), ("q", "finish")]),
+            (None, Mode::Edit { .. }) => key_hints(&[("Enter", "save"), ("Esc", "cancel")]),
+            (None, Mode::Query { input }) => Line::from(vec!["New query (Artist - Title): ".cyan(), input.with_cursor().into()]),
+            (None, Mode::Results { .. }) => key_hints(&[("↑↓", "move"), ("Enter", "use result"), ("Esc", "cancel")]),
+            (None, Mode::Duplicates { .. }) => key_hints(&[("↑↓", "move"), ("Enter", "merge into song"), ("d", "keep song, drop others"), ("k", "keep all"), ("Esc", "keep remaining")]),
+            (None, Mode::Command { input }) => Line::from(vec![":".cyan(), input.with_cursor().into(), format!("   {}", COMMAND_HELP).dark_gray()]),
+        };
+        frame.render_widget(status, status_area);
+    }
+}
+
+fn draw_results(frame: &mut Frame, results: &[LookupResult], list: &mut ListState) {
+
+    let items: Vec<ListItem> = results.iter().map(|result| {
+        let disambiguation = result.disambiguation.as_deref().map(|d| format!("; {}", d)).unwrap_or_default();
+        ListItem::new(Text::from(vec![
+            Line::from(vec![format!("{} ", result.year).blue(), format!("{}{}", result.detected_title, disambiguation).cyan()]),
+            Line::from(format!("     {}", result.justification).dark_gray()),
+        ]))
+    }).collect();
+
+    let area = centered(frame.area(), 80, (results.len() as u16 * 2 + 2).min(frame.area().height * 3 / 4));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(
+        List::new(items).block(Block::bordered().title(" Results ")).highlight_style(Style::new().reversed()),
+        area,
+        list,
+    );
+}
+
+fn draw_duplicates(frame: &mut Frame, songs: &[Song], duplicates: &Duplicates, group: usize, groups: usize, list: &mut ListState) {
+
+    let items: Vec<ListItem> = duplicates.songs.iter().map(|index| {
+        let song = &songs[*index];
+        ListItem::new(Text::from(vec![
+            Line::from(vec![format!("{} ", song.release_year).blue(), format!("{} - {}", song.artist, song.title).cyan()]),
+            Line::from(format!("     {} title: {}, {}", song.source.name(), song.raw_title, song.playback_url()).dark_gray()),
+        ]))
+    }).collect();
+
+    let kind = if duplicates.same_video { "same video" } else { "similar songs" };
+    let area = centered(frame.area(), 80, (duplicates.songs.len() as u16 * 2 + 2).min(frame.area().height * 3 / 4));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(
+        List::new(items).block(Block::bordered().title(format!(" Duplicates {}/{}: {} ", group + 1, groups, kind))).highlight_style(Style::new().reversed()),
+        area,
+        list,
+    );
+}
+
+fn centered(area: Rect, width_percent: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Percentage(width_percent)]).flex(Flex::Center).areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center).areas(area);
+    area
+}
+
+fn confidence_cell(confidence: Option<f32>) -> Cell<'static> {
+    let text = confidence.map(|c| format!("{:.0}%", c * 100.0)).unwrap_or_default();
+    match confidence {
+        Some(c) if c >= 0.8 => Cell::from(text).green(),
+        Some(c) if c >= DEFAULT_MIN_CONFIDENCE => Cell::from(text).yellow(),
+        _ => Cell::from(text).red(),
+    }
+}
+
+fn key_hints(hints: &[(&str, &str)]) -> Line<'static> {
+    Line::from(hints.iter().flat_map(|(key, action)| [
+        Span::from(format!(" {} ", key)).black().on_cyan(),
+        Span::from(format!(" {}  ", action)),
+    ]).collect::<Vec<_>>())
+}
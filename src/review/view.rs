@@ -0,0 +1,163 @@
+//! Filtering and sorting of the songs shown while reviewing.
+//!
+//! Both review screens take the same commands:
+//!
+//! | Command                                        | Shows                                                          |
+//! |------------------------------------------------|----------------------------------------------------------------|
+//! | `/queen`                                       | songs with "queen" in the artist, title, detected or raw title |
+//! | `year 1970-1979`, `year 1985`, `year -1960`    | songs with a release year in the range                         |
+//! | `nodetected`                                   | songs without a detected title                                 |
+//! | `sourceyear`                                   | songs whose release year is the year stated by the source      |
+//! | `low`, `low 80`                                | songs matched with less than the import's or the given percent |
+//! | `sort artist`, `sort title`, `sort year`       | the shown songs sorted, `sort` alone restores the import order |
+//! | `clear`                                        | all songs in import order                                      |
+//!
+//! Each filter narrows down the songs shown by the previous ones. The shown songs are only
+//! determined when a command is applied, so songs stay visible while they are being fixed.
+
+use std::error::Error;
+
+use crate::{metadata::confidence::DEFAULT_MIN_CONFIDENCE, Song};
+
+/// Short form of the commands for the review screens
+pub const COMMAND_HELP: &str = "/text, year 1970-1979, nodetected, sourceyear, low [percent], sort artist|title|year, clear";
+
+enum Filter {
+    /// Lowercase
+    Text(String),
+    Years(Option<i32>, Option<i32>),
+    NoDetectedTitle,
+    SourceYear,
+    LowConfidence(f32),
+}
+
+#[derive(Clone, Copy)]
+enum SortKey {
+    Artist,
+    Title,
+    Year,
+}
+
+#[derive(Default)]
+pub struct View {
+    filters: Vec<Filter>,
+    sort: Option<SortKey>,
+}
+
+impl Filter {
+
+    fn matches(&self, song: &Song) -> bool {
+        match self {
+            Filter::Text(text) => [Some(&song.artist), Some(&song.title), song.detected_title.as_ref(), Some(&song.raw_title)]
+                .into_iter()
+                .flatten()
+                .any(|field| field.to_lowercase().contains(text)),
+            Filter::Years(from, to) => from.is_none_or(|from| song.release_year >= from) && to.is_none_or(|to| song.release_year <= to),
+            Filter::NoDetectedTitle => song.detected_title.is_none(),
+            Filter::SourceYear => song.release_year == song.youtube_year,
+            // songs without a confidence were picked by hand or have no match, which nodetected finds
+            Filter::LowConfidence(threshold) => song.match_confidence.is_some_and(|c| c < *threshold),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Filter::Text(text) => format!("\"{}\"", text),
+            Filter::Years(Some(from), Some(to)) if from == to => format!("year {}", from),
+            Filter::Years(from, to) => format!("year {}-{}", from.map(|y| y.to_string()).unwrap_or_default(), to.map(|y| y.to_string()).unwrap_or_default()),
+            Filter::NoDetectedTitle => "no detected title".into(),
+            Filter::SourceYear => "source year".into(),
+            Filter::LowConfidence(threshold) => format!("match below {:.0}%", threshold * 100.0),
+        }
+    }
+}
+
+impl View {
+
+    /// Applies one of the commands listed in the module documentation.
+    pub fn apply(&mut self, command: &str) -> Result<(), Box<dyn Error>> {
+
+        let command = command.trim();
+        if let Some(text) = command.strip_prefix('/') {
+            if !text.trim().is_empty() {
+                self.filters.push(Filter::Text(text.trim().to_lowercase()));
+            }
+            return Ok(());
+        }
+
+        let (name, argument) = command.split_once(' ').map(|(n, a)| (n, a.trim())).unwrap_or((command, ""));
+        match (name, argument) {
+            ("year", range) => {
+                let parse = |year: &str| match year.trim() {
+                    "" => Ok(None),
+                    year => year.parse::<i32>().map(Some).map_err(|_| format!("{} is not a year", year)),
+                };
+                let (from, to) = match range.split_once('-') {
+                    Some((from, to)) => (parse(from)?, parse(to)?),
+                    None => (parse(range)?, parse(range)?),
+                };
+                if from.is_none() && to.is_none() {
+                    return Err("Enter a year or a range like 1970-1979".into());
+                }
+                self.filters.push(Filter::Years(from, to));
+            },
+            ("nodetected", "") => self.filters.push(Filter::NoDetectedTitle),
+            ("sourceyear", "") => self.filters.push(Filter::SourceYear),
+            ("low", "") => self.filters.push(Filter::LowConfidence(DEFAULT_MIN_CONFIDENCE)),
+            ("low", percent) => {
+                let percent: f32 = percent.trim_end_matches('%').parse().map_err(|_| format!("{} is not a percentage", percent))?;
+                self.filters.push(Filter::LowConfidence(percent / 100.0));
+            },
+            ("sort", key) => self.sort = match key {
+                "" => None,
+                "artist" => Some(SortKey::Artist),
+                "title" => Some(SortKey::Title),
+                "year" => Some(SortKey::Year),
+                _ => return Err(format!("Can't sort by {}, use artist, title or year", key).into()),
+            },
+            ("clear", "") => *self = View::default(),
+            _ => return Err(format!("Unknown command {}. Commands: {}", command, COMMAND_HELP).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Whether all songs are shown in import order
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.sort.is_none()
+    }
+
+    /// Indices of the shown songs in display order.
+    pub fn indices(&self, songs: &[Song]) -> Vec<usize> {
+
+        let mut indices: Vec<usize> = (0..songs.len())
+            .filter(|i| self.filters.iter().all(|f| f.matches(&songs[*i])))
+            .collect();
+
+        let by_artist = |a: &Song, b: &Song| a.artist.to_lowercase().cmp(&b.artist.to_lowercase());
+        let by_title = |a: &Song, b: &Song| a.title.to_lowercase().cmp(&b.title.to_lowercase());
+
+        if let Some(key) = self.sort {
+            indices.sort_by(|a, b| {
+                let (a, b) = (&songs[*a], &songs[*b]);
+                match key {
+                    SortKey::Artist => by_artist(a, b).then_with(|| by_title(a, b)),
+                    SortKey::Title => by_title(a, b).then_with(|| by_artist(a, b)),
+                    SortKey::Year => a.release_year.cmp(&b.release_year).then_with(|| by_artist(a, b)),
+                }
+            });
+        }
+
+        indices
+    }
+
+    /// The active filters and sorting, e.g. `year 1970-1979, no detected title, sorted by year`.
+    pub fn describe(&self) -> String {
+        let sort = self.sort.map(|key| match key {
+            SortKey::Artist => "sorted by artist".to_string(),
+            SortKey::Title => "sorted by title".to_string(),
+            SortKey::Year => "sorted by year".to_string(),
+        });
+        self.filters.iter().map(Filter::describe).chain(sort).collect::<Vec<_>>().join(", ")
+    }
+}
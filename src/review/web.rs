@@ -0,0 +1,323 @@
+//! Review in the browser, for curating a deck without the terminal.
+//!
+//! A small HTTP server serves the song table as a web page. Songs are edited in place, looked up
+//! again and previewed as cards. Every change is written to the song list file right away.
+//!
+//! | Request                          | Does                                                       |
+//! |----------------------------------|------------------------------------------------------------|
+//! | `GET /`                          | the review page                                            |
+//! | `GET /api/songs`                 | the songs and whether undo and redo are possible           |
+//! | `PUT /api/songs/<n>`             | sets `artist`, `title` and `release_year` of the JSON body |
+//! | `POST /api/songs/<n>/source-year`| uses the year stated by the source                         |
+//! | `POST /api/songs/<n>/query`      | looks up `artist` and `title` of the JSON body             |
+//! | `POST /api/songs/<n>/pick`       | uses the posted lookup result                              |
+//! | `GET /api/songs/<n>/card.svg`    | front and back of the card                                 |
+//! | `POST /api/undo`, `/api/redo`    | undoes or redoes the last change                           |
+//! | `POST /api/finish`               | ends the review                                            |
+//!
+//! Changes answer with the songs like `GET /api/songs`, errors with `{"error": "..."}`.
+//!
+//! Every request needs the random token of the link printed at the start, as `token` query
+//! parameter or `X-Review-Token` header, so other devices in the network and other pages open in
+//! the browser can't change the list. Changes must send JSON, which browsers only allow other sites
+//! after asking the server, and requests must name this server by its address, not by a domain
+//! that could point to it.
+
+use std::{error::Error, net::{IpAddr, SocketAddr}, path::{Path, PathBuf}, thread};
+use log::*;
+use reqwest::Client;
+use rusttype::Font;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{config, metadata::{LookupResult, MetadataLookup}, render::create_card_preview, serve::default_base_url, song_list::{csv::write_csv_song_list, edit_log::{EditLog, SongFields}, write_song_list_with_edits}, Song};
+
+use super::use_picked_result;
+
+pub const DEFAULT_REVIEW_PORT: u16 = 8090;
+
+const INDEX_HTML: &str = include_str!("web.html");
+
+struct WebReview<'a> {
+    client: &'a Client,
+    metadata: &'a MetadataLookup,
+    songs: &'a mut Vec<Song>,
+    log: &'a mut EditLog,
+    /// The song list file changes are saved to
+    path: PathBuf,
+    address: SocketAddr,
+    /// Required with every request
+    token: String,
+    font: Font<'static>,
+    icon: String,
+    design: String,
+}
+
+enum Reply {
+    Html(String),
+    Json(Value),
+    Svg(String),
+    Forbidden(&'static str),
+    NotFound,
+    Finish,
+}
+
+/// Body of `PUT /api/songs/<n>`, fields that are left out stay as they are
+#[derive(Deserialize)]
+struct SongChange {
+    artist: Option<String>,
+    title: Option<String>,
+    release_year: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct Query {
+    artist: String,
+    title: String,
+}
+
+/// Serves the review page at `address` until it is finished. `path` is the song list file, `design` the card background.
+/// JSON and CSV lists are saved in place, legacy lists to a `.json` file next to them.
+pub async fn review_songs_web(client: &Client, metadata: &MetadataLookup, songs: &mut Vec<Song>, log: &mut EditLog, path: &Path, design: &Path, address: SocketAddr) -> Result<(), Box<dyn Error>> {
+
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let path = match extension.as_str() {
+        "json" | "csv" => path.to_path_buf(),
+        // keep the original like migrate_legacy_song_lists does
+        _ => {
+            let target = path.with_extension("json");
+            if target.exists() {
+                return Err(format!("{} was already converted to {}, review that file instead", path.display(), target.display()).into());
+            }
+            target
+        },
+    };
+
+    let server = Server::http(address).map_err(|e| format!("Could not start the server on {}: {}", address, e))?;
+
+    let mut review = WebReview {
+        client,
+        metadata,
+        songs,
+        log,
+        path: path.clone(),
+        address,
+        token: new_token()?,
+        font: Font::try_from_vec(config::get().font_data()?).ok_or("Failed to load font")?,
+        icon: config::get().icon_svg()?,
+        design: std::fs::read_to_string(design).map_err(|e| format!("Error reading design {}: {}", design.display(), e))?,
+    };
+
+    let url = match address.ip().is_unspecified() {
+        true => default_base_url(address.port()),
+        false => format!("http://{}/", address),
+    };
+    info!("Review {} songs at {}?token={}", review.songs.len(), url, review.token);
+    info!("Changes are saved to {} right away. Finish the review on the page or press Ctrl+C to stop", path.display());
+
+    // tiny_http blocks while waiting, the requests are answered one after another here
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            if sender.send(request).is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(mut request) = receiver.recv().await {
+        let url = request.url().to_string();
+        let reply = review.handle(&mut request).await;
+        let finished = matches!(reply, Ok(Reply::Finish));
+        if let Err(e) = respond(request, reply) {
+            warn!("Failed to answer {}: {}", url, e);
+        }
+        if finished {
+            break;
+        }
+    }
+
+    info!("Review finished");
+
+    Ok(())
+}
+
+impl WebReview<'_> {
+
+    async fn handle(&mut self, request: &mut Request) -> Result<Reply, Box<dyn Error>> {
+
+        let (url, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+        let (url, query) = (url.to_string(), query.to_string());
+        let segments: Vec<&str> = url.trim_matches('/').split('/').collect();
+
+        let method = request.method().clone();
+
+        if !self.is_own_host(request) {
+            return Ok(Reply::Forbidden("Open the review with the address printed when it was started"));
+        }
+        let token = query.split('&').find_map(|p| p.strip_prefix("token=")).or(header(request, "X-Review-Token"));
+        if token != Some(self.token.as_str()) {
+            return Ok(Reply::Forbidden("Open the review with the link printed when it was started, it includes the access token"));
+        }
+        if method != Method::Get && !header(request, "Content-Type").is_some_and(|t| t.starts_with("application/json")) {
+            return Ok(Reply::Forbidden("Changes must be sent as application/json"));
+        }
+        let count = self.songs.len();
+        let song_index = |index: &str| index.parse::<usize>().ok().filter(|i| *i < count).ok_or(format!("There is no song {}", index));
+
+        let reply = match (method, segments.as_slice()) {
+            (Method::Get, [""]) => return Ok(Reply::Html(INDEX_HTML.replace("{{token}}", &self.token))),
+            (Method::Get, ["api", "songs"]) => return Ok(Reply::Json(self.state(None))),
+            (Method::Get, ["api", "songs", index, "card.svg"]) => {
+                let song = &self.songs[song_index(index)?];
+                return Ok(Reply::Svg(create_card_preview(song, &self.font, &self.icon, &self.design)));
+            },
+            (Method::Put, ["api", "songs", index]) => {
+                let index = song_index(index)?;
+                let change: SongChange = read_json(request)?;
+                self.change(index, change)
+            },
+            (Method::Post, ["api", "songs", index, "source-year"]) => {
+                let index = song_index(index)?;
+                let song = &mut self.songs[index];
+                let before = SongFields::of(song);
+                song.release_year = song.youtube_year;
+                song.year_justification = Some(song.source.year_description().into());
+                self.log.record(index, "Switch to source year", before, song);
+                format!("Using the {} {} for {}", song.source.year_description(), song.release_year, song.raw_title)
+            },
+            (Method::Post, ["api", "songs", index, "query"]) => {
+                song_index(index)?;
+                let query: Query = read_json(request)?;
+                let results = self.metadata.lookup(self.client, query.artist.trim(), query.title.trim(), None).await?;
+                return Ok(Reply::Json(json!({ "results": results })));
+            },
+            (Method::Post, ["api", "songs", index, "pick"]) => {
+                let index = song_index(index)?;
+                let result: LookupResult = read_json(request)?;
+                let song = &mut self.songs[index];
+                let before = SongFields::of(song);
+                use_picked_result(song, &result);
+                self.log.record(index, "New query", before, song);
+                format!("Using {} for {}", song.release_year, song.raw_title)
+            },
+            (Method::Post, ["api", "undo"]) => match self.log.undo(self.songs) {
+                Some(edit) => format!("Undid {}", edit.describe()),
+                None => return Err("Nothing to undo".into()),
+            },
+            (Method::Post, ["api", "redo"]) => match self.log.redo(self.songs) {
+                Some(edit) => format!("Redid {}", edit.describe()),
+                None => return Err("Nothing to redo".into()),
+            },
+            (Method::Post, ["api", "finish"]) => return Ok(Reply::Finish),
+            _ => return Ok(Reply::NotFound),
+        };
+
+        self.save()?;
+        Ok(Reply::Json(self.state(Some(reply))))
+    }
+
+    /// Applies the fields of `change` that differ, each as its own edit.
+    fn change(&mut self, index: usize, change: SongChange) -> String {
+
+        let song = &mut self.songs[index];
+
+        if let Some(artist) = change.artist {
+            let before = SongFields::of(song);
+            song.artist = artist.trim().to_string();
+            self.log.record(index, "Change artist", before, song);
+        }
+        if let Some(title) = change.title {
+            let before = SongFields::of(song);
+            song.title = title.trim().to_string();
+            self.log.record(index, "Change title", before, song);
+        }
+        if let Some(year) = change.release_year.filter(|y| *y != song.release_year) {
+            let before = SongFields::of(song);
+            song.release_year = year;
+            song.year_justification = Some("Set manually".into());
+            self.log.record(index, "Change year", before, song);
+        }
+
+        format!("Saved {} - {}", song.artist, song.title)
+    }
+
+    /// Whether the request names this server by IP address, or as localhost if it only listens there.
+    /// A domain name would mean a page of another site that resolves it to this address.
+    fn is_own_host(&self, request: &Request) -> bool {
+
+        let Some((name, port)) = header(request, "Host").and_then(|host| host.rsplit_once(':')) else {
+            return false;
+        };
+        if port != self.address.port().to_string() {
+            return false;
+        }
+        let own_host = match name.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => self.address.ip().is_unspecified() || ip == self.address.ip(),
+            Err(_) => name.eq_ignore_ascii_case("localhost") && self.address.ip().is_loopback(),
+        };
+
+        // pages only send requests to other sites with their own origin
+        own_host && header(request, "Origin").is_none_or(|origin| origin.strip_prefix("http://") == header(request, "Host"))
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        match self.path.extension().and_then(|e| e.to_str()) {
+            // CSV files have no place for the edit log
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => write_csv_song_list(&self.path, self.songs),
+            _ => write_song_list_with_edits(&self.path, self.songs, self.log.edits()),
+        }
+    }
+
+    fn state(&self, message: Option<String>) -> Value {
+        let songs: Vec<Value> = self.songs.iter().map(|song| {
+            let mut value = serde_json::to_value(song).unwrap_or_default();
+            value["source_name"] = song.source.name().into();
+            value["year_description"] = song.source.year_description().into();
+            value["link"] = song.playback_url().into();
+            value
+        }).collect();
+        json!({
+            "songs": songs,
+            "can_undo": self.log.can_undo(),
+            "can_redo": self.log.can_redo(),
+            "message": message,
+        })
+    }
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str())
+}
+
+/// 128 random bits as hex.
+fn new_token() -> Result<String, Box<dyn Error>> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("Could not create an access token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(request: &mut Request) -> Result<T, Box<dyn Error>> {
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body)?;
+    serde_json::from_str(&body).map_err(|e| format!("Invalid request: {}", e).into())
+}
+
+fn respond(request: Request, reply: Result<Reply, Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+
+    let (status, content_type, body) = match reply {
+        Ok(Reply::Html(html)) => (200, "text/html; charset=utf-8", html),
+        Ok(Reply::Json(json)) => (200, "application/json", json.to_string()),
+        Ok(Reply::Svg(svg)) => (200, "image/svg+xml", svg),
+        Ok(Reply::Finish) => (200, "application/json", json!({ "message": "Review finished, you can close this page" }).to_string()),
+        Ok(Reply::Forbidden(reason)) => (403, "application/json", json!({ "error": reason }).to_string()),
+        Ok(Reply::NotFound) => (404, "application/json", json!({ "error": "Not found" }).to_string()),
+        Err(e) => (400, "application/json", json!({ "error": e.to_string() }).to_string()),
+    };
+
+    let header = Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap();
+    request.respond(Response::from_string(body).with_status_code(status).with_header(header))?;
+
+    Ok(())
+}
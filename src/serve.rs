@@ -0,0 +1,139 @@
+//! A small HTTP server for the audio files of a local music folder, so the QR codes of cards
+//! imported with [`crate::sources::local::LocalFolder`] can be played on phones in the same network.
+
+use std::{error::Error, fs::File, io::{Read, Seek, SeekFrom}, net::{IpAddr, Ipv4Addr, UdpSocket}, path::{Path, PathBuf}, thread};
+use log::*;
+use percent_encoding::percent_decode_str;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+pub const DEFAULT_PORT: u16 = 8080;
+
+/// Link to the server on this machine's network address, as seen by other devices.
+pub fn default_base_url(port: u16) -> String {
+    format!("http://{}:{}/", local_ip(), port)
+}
+
+/// Address of the interface that routes to the internet. Connecting a UDP socket sends no packets.
+fn local_ip() -> IpAddr {
+    let ip = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).map(|_| socket))
+        .and_then(|socket| socket.local_addr());
+    match ip {
+        Ok(address) => address.ip(),
+        Err(e) => {
+            warn!("Could not determine the network address, cards will only play on this machine: {}", e);
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        }
+    }
+}
+
+/// Serves the files below `root` until the process is stopped.
+pub fn serve_folder(root: &Path, port: u16) -> Result<(), Box<dyn Error>> {
+
+    if !root.is_dir() {
+        return Err(format!("{} is not a directory", root.display()).into());
+    }
+    let root = root.canonicalize()?;
+
+    let server = Server::http((Ipv4Addr::UNSPECIFIED, port)).map_err(|e| format!("Could not start the server on port {}: {}", port, e))?;
+
+    info!("Serving {} at {}", root.display(), default_base_url(port));
+    info!("Press Ctrl+C to stop");
+
+    for request in server.incoming_requests() {
+        let root = root.clone();
+        // a playing song keeps its connection open, so every request gets its own thread
+        thread::spawn(move || {
+            let url = request.url().to_string();
+            if let Err(e) = respond(&root, request) {
+                warn!("Failed to answer {}: {}", url, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn respond(root: &Path, request: Request) -> Result<(), Box<dyn Error>> {
+
+    if !matches!(request.method(), Method::Get | Method::Head) {
+        return Ok(request.respond(Response::empty(405))?);
+    }
+
+    let Some(path) = resolve(root, request.url()) else {
+        return Ok(request.respond(Response::empty(404))?);
+    };
+
+    let mut file = File::open(&path)?;
+    let length = file.metadata()?.len();
+
+    let content_type = Header::from_bytes(&b"Content-Type"[..], content_type(&path)).unwrap();
+    let accept_ranges = Header::from_bytes(&b"Accept-Ranges"[..], &b"bytes"[..]).unwrap();
+
+    // mobile browsers only play audio that supports range requests
+    let range = request.headers().iter()
+        .find(|h| h.field.equiv("Range"))
+        .map(|h| parse_range(h.value.as_str(), length));
+
+    match range {
+        None => {
+            let response = Response::new(StatusCode(200), vec![content_type, accept_ranges], file, Some(length as usize), None);
+            request.respond(response)?;
+        },
+        Some(Some((start, end))) => {
+            file.seek(SeekFrom::Start(start))?;
+            let content_range = Header::from_bytes(&b"Content-Range"[..], format!("bytes {}-{}/{}", start, end, length)).unwrap();
+            let size = end - start + 1;
+            let response = Response::new(StatusCode(206), vec![content_type, accept_ranges, content_range], file.take(size), Some(size as usize), None);
+            request.respond(response)?;
+        },
+        Some(None) => {
+            let content_range = Header::from_bytes(&b"Content-Range"[..], format!("bytes */{}", length)).unwrap();
+            request.respond(Response::empty(416).with_header(content_range))?;
+        },
+    }
+
+    Ok(())
+}
+
+/// The file a request path points to, `None` if it doesn't exist or lies outside of `root`.
+fn resolve(root: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next()?;
+    let mut resolved = root.to_path_buf();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        let segment = percent_decode_str(segment).decode_utf8().ok()?;
+        if segment == "." || segment == ".." || segment.contains(['/', '\\']) {
+            return None;
+        }
+        resolved.push(segment.as_ref());
+    }
+    // symbolic links could still point outside
+    let resolved = resolved.canonicalize().ok()?;
+    (resolved.starts_with(root) && resolved.is_file()).then_some(resolved)
+}
+
+/// First and last byte of a single range like "bytes=0-1023", "bytes=1024-" or "bytes=-512".
+/// `None` if it can't be satisfied.
+fn parse_range(value: &str, length: u64) -> Option<(u64, u64)> {
+    let (start, end) = value.trim().strip_prefix("bytes=")?.split(',').next()?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => (length.checked_sub(suffix.parse::<u64>().ok()?.min(length))?, length.checked_sub(1)?),
+        (start, "") => (start.parse().ok()?, length.checked_sub(1)?),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(length.checked_sub(1)?)),
+    };
+    (start <= end).then_some((start, end))
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase().as_str() {
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "aif" | "aiff" => "audio/aiff",
+        "flac" => "audio/flac",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
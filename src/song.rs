@@ -0,0 +1,38 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::sources::SourceKind;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Song {
+    pub artist: String,
+    pub title: String,
+    pub release_year: i32,
+    /// Year stated by the source, see [`SourceKind::year_description`]. Named after the first source.
+    pub youtube_year: i32,
+    /// ID of the track at the source
+    pub video_id: String,
+    #[serde(default, skip_serializing_if = "SourceKind::is_default")]
+    pub source: SourceKind,
+    pub raw_title: String,
+    pub detected_title: Option<String>,
+    /// International Standard Recording Code, used for exact lookups when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isrc: Option<String>,
+    /// Where the release year came from, e.g. the MusicBrainz release it was taken from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year_justification: Option<String>,
+    /// How well `detected_title` matches the queried artist and title, from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_confidence: Option<f32>,
+    /// Additional columns from imported spreadsheets, kept so they survive a round trip.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl Song {
+    /// Where the QR code on the card back points.
+    pub fn playback_url(&self) -> String {
+        self.source.playback_url(&self.video_id)
+    }
+}
@@ -0,0 +1,257 @@
+//! CSV import and export so song lists can be edited in a spreadsheet.
+//!
+//! Columns are matched by header name, ignoring case, spaces, `_` and `-`:
+//!
+//! | Field            | Accepted headers                 | Required |
+//! |------------------|----------------------------------|----------|
+//! | `artist`         | artist                           | yes      |
+//! | `title`          | title                            | yes      |
+//! | `release_year`   | release year, year               | yes      |
+//! | `youtube_year`   | youtube year                     | no, defaults to the release year |
+//! | `video_id`       | video id, youtube id             | no       |
+//! | `source`         | source                           | no, `youtube`, `spotify` or `local`, defaults to `youtube` |
+//! | `raw_title`      | raw title                        | no, defaults to `artist - title` |
+//! | `detected_title` | detected title                   | no       |
+//! | `isrc`           | isrc                             | no       |
+//! | `year_justification` | year justification, justification | no  |
+//! | `match_confidence` | match confidence, confidence   | no, between 0 and 1 |
+//!
+//! Any other column is kept in [`Song::extra`] and written back on export.
+
+use std::{collections::BTreeSet, error::Error, path::Path};
+
+use clap::ValueEnum;
+
+use crate::{sources::SourceKind, Song};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Artist,
+    Title,
+    ReleaseYear,
+    YoutubeYear,
+    VideoId,
+    Source,
+    RawTitle,
+    DetectedTitle,
+    Isrc,
+    YearJustification,
+    MatchConfidence,
+    Extra,
+}
+
+const KNOWN_HEADERS: [&str; 11] = ["artist", "title", "release_year", "youtube_year", "video_id", "source", "raw_title", "detected_title", "isrc", "year_justification", "match_confidence"];
+
+fn column_for_header(header: &str) -> Column {
+    let normalized: String = header.chars().filter(|c| !matches!(c, ' ' | '_' | '-')).collect::<String>().to_lowercase();
+    match normalized.as_str() {
+        "artist" => Column::Artist,
+        "title" => Column::Title,
+        "releaseyear" | "year" => Column::ReleaseYear,
+        "youtubeyear" => Column::YoutubeYear,
+        "videoid" | "youtubeid" => Column::VideoId,
+        "source" => Column::Source,
+        "rawtitle" => Column::RawTitle,
+        "detectedtitle" => Column::DetectedTitle,
+        "isrc" => Column::Isrc,
+        "yearjustification" | "justification" => Column::YearJustification,
+        "matchconfidence" | "confidence" => Column::MatchConfidence,
+        _ => Column::Extra,
+    }
+}
+
+/// Reads a CSV song list. Every malformed row is reported, nothing is skipped.
+pub fn read_csv_song_list(path: &Path) -> Result<Vec<Song>, Box<dyn Error>> {
+
+    let mut reader = ::csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+
+    let headers = reader.headers()?.clone();
+    let columns: Vec<Column> = headers.iter().map(column_for_header).collect();
+
+    for (required, name) in [(Column::Artist, "artist"), (Column::Title, "title"), (Column::ReleaseYear, "release year")] {
+        if !columns.contains(&required) {
+            return Err(format!("{}: missing {} column", path.display(), name).into());
+        }
+    }
+
+    let mut songs = Vec::new();
+    let mut problems = Vec::new();
+
+    for (index, record) in reader.records().enumerate() {
+        // header is line 1
+        let line = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                problems.push(format!("line {}: {}", line, e));
+                continue;
+            }
+        };
+
+        let mut song = Song::default();
+        let mut youtube_year = None;
+
+        for (field_index, value) in record.iter().enumerate() {
+            let Some(column) = columns.get(field_index) else {
+                problems.push(format!("line {}: more fields than headers", line));
+                break;
+            };
+            match column {
+                Column::Artist => song.artist = value.to_string(),
+                Column::Title => song.title = value.to_string(),
+                Column::ReleaseYear => match value.trim().parse::<i32>() {
+                    Ok(year) => song.release_year = year,
+                    Err(_) => problems.push(format!("line {}: invalid release year {:?}", line, value)),
+                },
+                Column::YoutubeYear => if !value.trim().is_empty() {
+                    match value.trim().parse::<i32>() {
+                        Ok(year) => youtube_year = Some(year),
+                        Err(_) => problems.push(format!("line {}: invalid YouTube year {:?}", line, value)),
+                    }
+                },
+                Column::VideoId => song.video_id = value.to_string(),
+                Column::Source => if !value.trim().is_empty() {
+                    match SourceKind::from_str(value.trim(), true) {
+                        Ok(source) => song.source = source,
+                        Err(_) => problems.push(format!("line {}: unknown source {:?}", line, value)),
+                    }
+                },
+                Column::RawTitle => song.raw_title = value.to_string(),
+                Column::DetectedTitle => song.detected_title = Some(value.to_string()).filter(|t| !t.is_empty()),
+                Column::Isrc => song.isrc = Some(value.trim().to_string()).filter(|i| !i.is_empty()),
+                Column::YearJustification => song.year_justification = Some(value.to_string()).filter(|j| !j.is_empty()),
+                Column::MatchConfidence => if !value.trim().is_empty() {
+                    match value.trim().parse::<f32>() {
+                        Ok(confidence) if (0.0..=1.0).contains(&confidence) => song.match_confidence = Some(confidence),
+                        _ => problems.push(format!("line {}: invalid match confidence {:?}", line, value)),
+                    }
+                },
+                Column::Extra => if !value.is_empty() {
+                    song.extra.insert(headers[field_index].to_string(), value.to_string());
+                },
+            }
+        }
+
+        song.youtube_year = youtube_year.unwrap_or(song.release_year);
+        if song.raw_title.is_empty() {
+            song.raw_title = format!("{} - {}", song.artist, song.title);
+        }
+
+        songs.push(song);
+    }
+
+    if !problems.is_empty() {
+        return Err(format!("{}: {} malformed rows:\n  {}", path.display(), problems.len(), problems.join("\n  ")).into());
+    }
+
+    Ok(songs)
+}
+
+/// Writes all song fields plus every extra field that appears in any song.
+pub fn write_csv_song_list(path: &Path, songs: &[Song]) -> Result<(), Box<dyn Error>> {
+
+    let extra_headers: BTreeSet<&str> = songs.iter().flat_map(|s| s.extra.keys().map(String::as_str)).collect();
+
+    let mut writer = ::csv::Writer::from_path(path)?;
+
+    writer.write_record(KNOWN_HEADERS.iter().copied().chain(extra_headers.iter().copied()))?;
+
+    for song in songs {
+        let mut record = vec![
+            song.artist.clone(),
+            song.title.clone(),
+            song.release_year.to_string(),
+            song.youtube_year.to_string(),
+            song.video_id.clone(),
+            song.source.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default(),
+            song.raw_title.clone(),
+            song.detected_title.clone().unwrap_or_default(),
+            song.isrc.clone().unwrap_or_default(),
+            song.year_justification.clone().unwrap_or_default(),
+            song.match_confidence.map(|c| c.to_string()).unwrap_or_default(),
+        ];
+        record.extend(extra_headers.iter().map(|h| song.extra.get(*h).cloned().unwrap_or_default()));
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("carnister-csv-{}-{}.csv", std::process::id(), name))
+    }
+
+    #[test]
+    fn round_trip_keeps_all_fields() {
+        let mut extra = std::collections::BTreeMap::new();
+        extra.insert("Notes".to_string(), "first dance, \"slow\"".to_string());
+        let songs = vec![
+            Song { artist: "Queen".into(), title: "Bohemian Rhapsody".into(), release_year: 1975, youtube_year: 2008, video_id: "fJ9rUzIMcZQ".into(),
+                raw_title: "Queen – Bohemian Rhapsody (Official Video)".into(), detected_title: Some("Queen - Bohemian Rhapsody".into()),
+                isrc: Some("GBUM71029604".into()), year_justification: Some("A Night at the Opera".into()), match_confidence: Some(0.8765), extra, ..Default::default() },
+            Song { artist: "Kate Bush".into(), title: "Running Up That Hill".into(), release_year: 1985, youtube_year: 2018, video_id: "4".into(),
+                source: SourceKind::Spotify, raw_title: "Kate Bush - Running Up That Hill".into(), ..Default::default() },
+        ];
+
+        let path = temp_path("round-trip");
+        write_csv_song_list(&path, &songs).unwrap();
+        let read = read_csv_song_list(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.len(), 2);
+        for (song, read) in songs.iter().zip(&read) {
+            assert_eq!(read.artist, song.artist);
+            assert_eq!(read.title, song.title);
+            assert_eq!(read.release_year, song.release_year);
+            assert_eq!(read.youtube_year, song.youtube_year);
+            assert_eq!(read.video_id, song.video_id);
+            assert!(read.source == song.source);
+            assert_eq!(read.raw_title, song.raw_title);
+            assert_eq!(read.detected_title, song.detected_title);
+            assert_eq!(read.isrc, song.isrc);
+            assert_eq!(read.year_justification, song.year_justification);
+            assert_eq!(read.match_confidence, song.match_confidence);
+            assert_eq!(read.extra, song.extra);
+        }
+    }
+
+    #[test]
+    fn reads_spreadsheet_headers_and_defaults() {
+        let path = temp_path("headers");
+        std::fs::write(&path, "Artist,Title,Year,Decade\nABBA,Dancing Queen,1976,70s\n").unwrap();
+        let songs = read_csv_song_list(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(songs.len(), 1);
+        assert_eq!(songs[0].release_year, 1976);
+        assert_eq!(songs[0].youtube_year, 1976);
+        assert_eq!(songs[0].raw_title, "ABBA - Dancing Queen");
+        assert_eq!(songs[0].extra.get("Decade").map(String::as_str), Some("70s"));
+    }
+
+    #[test]
+    fn reports_malformed_rows() {
+        let path = temp_path("malformed");
+        std::fs::write(&path, "artist,title,release year,source,confidence\nA,T,1975,youtube,0.5\nB,T,nineteen,radio,2\n").unwrap();
+        let result = read_csv_song_list(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let Err(error) = result else { panic!("malformed rows were accepted") };
+        let error = error.to_string();
+        assert!(error.contains("3 malformed rows"), "{}", error);
+        assert!(error.contains("line 3: invalid release year"), "{}", error);
+        assert!(error.contains("line 3: unknown source"), "{}", error);
+        assert!(error.contains("line 3: invalid match confidence"), "{}", error);
+
+        std::fs::write(&path, "artist,year\nA,1975\n").unwrap();
+        let result = read_csv_song_list(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err_and(|e| e.to_string().ends_with("missing title column")));
+    }
+}
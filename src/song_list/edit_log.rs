@@ -0,0 +1,315 @@
+//! Log of the changes made while reviewing, saved with the song list.
+//!
+//! Every edit records the reviewed fields of the song before and after the change, who made it
+//! and when. The year justification of the `after` fields says where a new year came from.
+//! Songs are referred to by their position at the time of the edit. Edits that remove a song from
+//! the list keep all of it in `removed`, so undoing them puts it back.
+//!
+//! ```json
+//! {
+//!   "song": 12,
+//!   "action": "Change year",
+//!   "user": "alex",
+//!   "time": "2025-03-01T18:22:05+01:00",
+//!   "before": { "artist": "Queen", "title": "Bohemian Rhapsody", "release_year": 2008 },
+//!   "after": { "artist": "Queen", "title": "Bohemian Rhapsody", "release_year": 1975, "year_justification": "Set manually" }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Song;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Edit {
+    /// Position of the song in the list when the edit was made
+    pub song: usize,
+    /// What was done, e.g. "Change year"
+    pub action: String,
+    /// Login name of whoever made the edit
+    pub user: String,
+    /// RFC 3339 date and time
+    pub time: String,
+    pub before: SongFields,
+    pub after: SongFields,
+    /// The song, if the edit removed it from the list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<Song>,
+}
+
+/// The fields of a [`Song`] that can be changed while reviewing.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SongFields {
+    pub artist: String,
+    pub title: String,
+    pub release_year: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected_title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year_justification: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_confidence: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isrc: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl SongFields {
+
+    pub fn of(song: &Song) -> Self {
+        SongFields {
+            artist: song.artist.clone(),
+            title: song.title.clone(),
+            release_year: song.release_year,
+            detected_title: song.detected_title.clone(),
+            year_justification: song.year_justification.clone(),
+            match_confidence: song.match_confidence,
+            isrc: song.isrc.clone(),
+            extra: song.extra.clone(),
+        }
+    }
+
+    fn apply(&self, song: &mut Song) {
+        song.artist = self.artist.clone();
+        song.title = self.title.clone();
+        song.release_year = self.release_year;
+        song.detected_title = self.detected_title.clone();
+        song.year_justification = self.year_justification.clone();
+        song.match_confidence = self.match_confidence;
+        song.isrc = self.isrc.clone();
+        song.extra = self.extra.clone();
+    }
+}
+
+/// Edits with undo and redo. Edits loaded with a song list can be undone as well.
+#[derive(Default)]
+pub struct EditLog {
+    edits: Vec<Edit>,
+    /// Number of edits in effect, the ones after it were undone
+    applied: usize,
+}
+
+impl EditLog {
+
+    pub fn new(edits: Vec<Edit>) -> Self {
+        let applied = edits.len();
+        EditLog { edits, applied }
+    }
+
+    /// Records the change of the song at `index` since `before`, if anything changed.
+    /// Undone edits can't be redone afterwards.
+    pub fn record(&mut self, index: usize, action: &str, before: SongFields, song: &Song) {
+
+        let after = SongFields::of(song);
+        if after == before {
+            return;
+        }
+        self.push(index, action, before, after, None);
+    }
+
+    /// Removes the song at `index` from the list as an edit that can be undone.
+    /// Undone edits can't be redone afterwards.
+    pub fn remove(&mut self, songs: &mut Vec<Song>, index: usize, action: &str) {
+        let song = songs.remove(index);
+        let fields = SongFields::of(&song);
+        self.push(index, action, fields.clone(), fields, Some(song));
+    }
+
+    fn push(&mut self, index: usize, action: &str, before: SongFields, after: SongFields, removed: Option<Song>) {
+        self.edits.truncate(self.applied);
+        self.edits.push(Edit {
+            song: index,
+            action: action.to_string(),
+            user: current_user(),
+            time: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            before,
+            after,
+            removed,
+        });
+        self.applied = self.edits.len();
+    }
+
+    /// Reverts the last edit in effect and returns it.
+    pub fn undo(&mut self, songs: &mut Vec<Song>) -> Option<&Edit> {
+        let edit = self.edits[..self.applied].last()?;
+        match &edit.removed {
+            Some(song) if edit.song <= songs.len() => songs.insert(edit.song, song.clone()),
+            Some(_) => return None,
+            None => edit.before.apply(songs.get_mut(edit.song)?),
+        }
+        self.applied -= 1;
+        Some(edit)
+    }
+
+    /// Applies the last undone edit again and returns it.
+    pub fn redo(&mut self, songs: &mut Vec<Song>) -> Option<&Edit> {
+        let edit = self.edits.get(self.applied)?;
+        match &edit.removed {
+            Some(_) if edit.song < songs.len() => {
+                songs.remove(edit.song);
+            },
+            Some(_) => return None,
+            None => edit.after.apply(songs.get_mut(edit.song)?),
+        }
+        self.applied += 1;
+        Some(edit)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.applied > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.applied < self.edits.len()
+    }
+
+    /// The edits in effect, oldest first
+    pub fn edits(&self) -> &[Edit] {
+        &self.edits[..self.applied]
+    }
+}
+
+impl Edit {
+    /// e.g. `Change year of Queen - Bohemian Rhapsody`
+    pub fn describe(&self) -> String {
+        format!("{} of {} - {}", self.action, self.after.artist, self.after.title)
+    }
+}
+
+/// The edits for the list reordered so the song at `i` moves to `position[i]`.
+/// Songs removed by an edit return in front of the song that followed them.
+pub fn reorder_edits(edits: &[Edit], position: &[usize]) -> Vec<Edit> {
+
+    // positions in the list as it was after the edit at hand, going back from the newest
+    let mut position = position.to_vec();
+    let mut reordered: Vec<Edit> = edits.iter().rev().map(|edit| {
+        if edit.removed.is_some() && edit.song <= position.len() {
+            let slot = position.get(edit.song).copied().unwrap_or(position.len());
+            for p in position.iter_mut().filter(|p| **p >= slot) {
+                *p += 1;
+            }
+            position.insert(edit.song, slot);
+        }
+        Edit { song: position.get(edit.song).copied().unwrap_or(edit.song), ..edit.clone() }
+    }).collect();
+
+    reordered.reverse();
+    reordered
+}
+
+fn current_user() -> String {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "unknown".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song_list::{load_song_list_with_edits, write_song_list_with_edits};
+
+    fn song(artist: &str, year: i32) -> Song {
+        Song { artist: artist.into(), title: format!("{} song", artist), release_year: year, ..Default::default() }
+    }
+
+    fn change_year(log: &mut EditLog, songs: &mut [Song], index: usize, year: i32) {
+        let before = SongFields::of(&songs[index]);
+        songs[index].release_year = year;
+        log.record(index, "Change year", before, &songs[index]);
+    }
+
+    fn artists(songs: &[Song]) -> Vec<(&str, i32)> {
+        songs.iter().map(|s| (s.artist.as_str(), s.release_year)).collect()
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_fields() {
+        let mut songs = vec![song("A", 1990), song("B", 1970)];
+        let mut log = EditLog::default();
+
+        let before = SongFields::of(&songs[1]);
+        songs[1].title = "Other".into();
+        songs[1].year_justification = Some("Set manually".into());
+        log.record(1, "Change title", before, &songs[1]);
+        change_year(&mut log, &mut songs, 1, 1971);
+        // nothing changed, nothing recorded
+        change_year(&mut log, &mut songs, 0, 1990);
+        assert_eq!(log.edits().len(), 2);
+
+        assert_eq!(log.undo(&mut songs).unwrap().describe(), "Change year of B - Other");
+        assert_eq!(artists(&songs), [("A", 1990), ("B", 1970)]);
+        log.undo(&mut songs);
+        assert_eq!(songs[1].title, "B song");
+        assert_eq!(songs[1].year_justification, None);
+        assert!(log.undo(&mut songs).is_none());
+
+        log.redo(&mut songs);
+        assert_eq!(songs[1].title, "Other");
+        assert!(log.can_redo());
+
+        // a new edit drops the undone ones
+        change_year(&mut log, &mut songs, 0, 1989);
+        assert!(!log.can_redo());
+        assert!(log.redo(&mut songs).is_none());
+        assert_eq!(log.edits().iter().map(|e| e.action.as_str()).collect::<Vec<_>>(), ["Change title", "Change year"]);
+    }
+
+    #[test]
+    fn reordered_edits_follow_their_songs() {
+        let mut songs = vec![song("A", 1990), song("B", 1970), song("C", 1980)];
+        let mut log = EditLog::default();
+        change_year(&mut log, &mut songs, 0, 1991);
+        change_year(&mut log, &mut songs, 2, 1981);
+
+        // sorted by year: B, C, A
+        let reordered = reorder_edits(log.edits(), &[2, 0, 1]);
+        assert_eq!(reordered.iter().map(|e| e.song).collect::<Vec<_>>(), [2, 1]);
+    }
+
+    #[test]
+    fn undo_puts_removed_songs_back() {
+        let mut songs = vec![song("A", 1990), song("B", 1970), song("C", 1980)];
+        let mut log = EditLog::default();
+
+        change_year(&mut log, &mut songs, 0, 1991);
+        log.remove(&mut songs, 0, "Drop duplicate");
+        assert_eq!(artists(&songs), [("B", 1970), ("C", 1980)]);
+
+        assert_eq!(log.undo(&mut songs).unwrap().action, "Drop duplicate");
+        assert_eq!(artists(&songs), [("A", 1991), ("B", 1970), ("C", 1980)]);
+        log.undo(&mut songs);
+        assert_eq!(artists(&songs), [("A", 1990), ("B", 1970), ("C", 1980)]);
+
+        log.redo(&mut songs);
+        log.redo(&mut songs);
+        assert_eq!(artists(&songs), [("B", 1970), ("C", 1980)]);
+        assert!(!log.can_redo());
+        assert_eq!(log.edits().len(), 2);
+    }
+
+    #[test]
+    fn saved_edits_follow_the_sorted_songs() {
+        let mut songs = vec![song("A", 1990), song("B", 1970), song("C", 1980)];
+        let mut log = EditLog::default();
+
+        change_year(&mut log, &mut songs, 2, 1960);
+        log.remove(&mut songs, 0, "Drop duplicate");
+        change_year(&mut log, &mut songs, 0, 1975);
+
+        let path = std::env::temp_dir().join(format!("carnister-edit-log-{}.json", std::process::id()));
+        write_song_list_with_edits(&path, &songs, log.edits()).unwrap();
+        let (mut songs, edits) = load_song_list_with_edits(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(artists(&songs), [("C", 1960), ("B", 1975)]);
+        let mut log = EditLog::new(edits);
+        log.undo(&mut songs);
+        assert_eq!(artists(&songs), [("C", 1960), ("B", 1970)]);
+        log.undo(&mut songs);
+        assert_eq!(artists(&songs), [("C", 1960), ("A", 1990), ("B", 1970)]);
+        log.undo(&mut songs);
+        assert_eq!(artists(&songs), [("C", 1980), ("A", 1990), ("B", 1970)]);
+        assert!(!log.can_undo());
+    }
+}
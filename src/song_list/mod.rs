@@ -0,0 +1,329 @@
+//! Reading and writing song list files.
+//!
+//! Song lists are stored as pretty-printed JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "songs": [
+//!     {
+//!       "artist": "Queen",
+//!       "title": "Bohemian Rhapsody",
+//!       "release_year": 1975,
+//!       "youtube_year": 2008,
+//!       "video_id": "fJ9rUzIMcZQ",
+//!       "raw_title": "Queen – Bohemian Rhapsody (Official Video)",
+//!       "detected_title": "Queen - Bohemian Rhapsody"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `version` is bumped whenever the song fields change incompatibly. Fields added later
+//! are optional, so older files keep loading. Lists that were reviewed also contain the
+//! [`edits`](self::edit_log) made to them.
+//!
+//! Files written before the versioned format (`.txt`, one song per line with fields
+//! separated by the ASCII unit separator) are still read and can be converted with
+//! [`migrate_legacy_song_lists`]. CSV files are handled by the [`csv`](self::csv) module.
+
+pub mod csv;
+pub mod edit_log;
+
+use std::{error::Error, fs::File, io::{BufWriter, Write}, path::{Path, PathBuf}};
+use colored::Colorize;
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, Song};
+
+use self::{csv::read_csv_song_list, edit_log::{reorder_edits, Edit}};
+
+/// Version written by [`write_song_list`]. Files with a higher version are rejected.
+pub const SONG_LIST_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct SongListFileRef<'a> {
+    version: u32,
+    songs: &'a [&'a Song],
+    #[serde(skip_serializing_if = "<[Edit]>::is_empty")]
+    edits: &'a [Edit],
+}
+
+#[derive(Deserialize)]
+struct SongListFile {
+    version: u32,
+    songs: Vec<Song>,
+    #[serde(default)]
+    edits: Vec<Edit>,
+}
+
+pub fn list_song_lists() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(&config::get().song_list_dir)? {
+        match entry {
+            Ok(entry) => files.push(entry.path()),
+            Err(_) => println!("{}", "  Error".red())
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Loads a CSV song list, a song list in the versioned format or, failing that, the legacy line format.
+pub fn load_song_list(path: &Path) -> Result<Vec<Song>, Box<dyn Error>> {
+    load_song_list_with_edits(path).map(|(songs, _)| songs)
+}
+
+/// Like [`load_song_list`], also returning the edit log. Only the versioned format has one.
+pub fn load_song_list_with_edits(path: &Path) -> Result<(Vec<Song>, Vec<Edit>), Box<dyn Error>> {
+
+    if path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("csv")) {
+        return Ok((read_csv_song_list(path)?, Vec::new()));
+    }
+
+    let content = std::fs::read_to_string(path)?;
+
+    if content.trim_start().starts_with('{') {
+        let file: SongListFile = serde_json::from_str(&content)
+            .map_err(|e| format!("{}: invalid song list: {}", path.display(), e))?;
+        if file.version > SONG_LIST_VERSION {
+            return Err(format!("{}: song list version {} is newer than the supported version {}", path.display(), file.version, SONG_LIST_VERSION).into());
+        }
+        return Ok((file.songs, file.edits));
+    }
+
+    let songs = parse_legacy_song_list(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok((songs, Vec::new()))
+}
+
+/// Writes the songs in the versioned format, sorted by release year.
+pub fn write_song_list(path: &Path, songs: &[Song]) -> Result<(), Box<dyn Error>> {
+    write_song_list_with_edits(path, songs, &[])
+}
+
+/// Like [`write_song_list`], the songs the edits refer to are updated to their sorted positions.
+pub fn write_song_list_with_edits(path: &Path, songs: &[Song], edits: &[Edit]) -> Result<(), Box<dyn Error>> {
+
+    let mut order: Vec<usize> = (0..songs.len()).collect();
+    order.sort_by(|a, b| i32::cmp(&songs[*a].release_year, &songs[*b].release_year));
+
+    let mut position = vec![0; songs.len()];
+    for (new, old) in order.iter().enumerate() {
+        position[*old] = new;
+    }
+    let edits = reorder_edits(edits, &position);
+    let songs: Vec<&Song> = order.iter().map(|i| &songs[*i]).collect();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &SongListFileRef { version: SONG_LIST_VERSION, songs: &songs, edits: &edits })?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Sorts by release year and writes the list to a new timestamped file. Returns the file name without extension.
+pub fn save_song_list(songs: &[Song]) -> Result<String, Box<dyn Error>> {
+    save_song_list_with_edits(songs, &[])
+}
+
+/// Like [`save_song_list`], keeping the edit log.
+pub fn save_song_list_with_edits(songs: &[Song], edits: &[Edit]) -> Result<String, Box<dyn Error>> {
+
+    info!("Saving List...");
+
+    let file_name = format!("song-list-{}", chrono::Local::now().format("%Y-%m-%d-%H:%M:%S"));
+    let path = config::get().song_list_dir.join(format!("{}.json", file_name));
+    write_song_list_with_edits(&path, songs, edits)?;
+
+    info!("Saved to {}", path.display());
+
+    Ok(file_name)
+}
+
+/// Converts every legacy `.txt` list in the song list directory that has no `.json` counterpart yet.
+/// The original files are kept. Returns the paths of the written files.
+pub fn migrate_legacy_song_lists() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+
+    let mut migrated = Vec::new();
+
+    for path in list_song_lists()? {
+        if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+            continue;
+        }
+        let target = path.with_extension("json");
+        if target.exists() {
+            continue;
+        }
+        match load_song_list(&path) {
+            Ok(songs) => {
+                write_song_list(&target, &songs)?;
+                info!("Migrated {} songs from {} to {}", songs.len(), path.display(), target.display());
+                migrated.push(target);
+            },
+            Err(e) => error!("Could not migrate {}", e),
+        }
+    }
+
+    Ok(migrated)
+}
+
+/// Parses the unit-separator format. Every malformed line is reported, nothing is skipped.
+fn parse_legacy_song_list(content: &str) -> Result<Vec<Song>, String> {
+
+    let mut songs = Vec::new();
+    let mut problems = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_legacy_line(line) {
+            Ok(song) => songs.push(song),
+            Err(e) => problems.push(format!("line {}: {}", index + 1, e)),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(format!("{} malformed lines in legacy song list:\n  {}", problems.len(), problems.join("\n  ")));
+    }
+
+    Ok(songs)
+}
+
+fn parse_legacy_line(line: &str) -> Result<Song, String> {
+
+    let parts: Vec<&str> = line.split(char::from(31)).collect();
+
+    if parts.len() != 7 {
+        return Err(format!("expected 7 fields, found {}", parts.len()));
+    }
+
+    Ok(Song {
+        artist: parts[0].to_string(),
+        title: parts[1].to_string(),
+        release_year: parts[2].parse::<i32>().map_err(|_| format!("invalid release year {:?}", parts[2]))?,
+        youtube_year: parts[3].parse::<i32>().map_err(|_| format!("invalid YouTube year {:?}", parts[3]))?,
+        video_id: parts[4].to_string(),
+        raw_title: parts[5].to_string(),
+        detected_title: parse_option_string(parts[6])?,
+        ..Default::default()
+    })
+}
+
+/// Parses the `{:?}` output of an `Option<String>`, undoing the string escapes.
+fn parse_option_string(input: &str) -> Result<Option<String>, String> {
+
+    if input == "None" {
+        return Ok(None);
+    }
+
+    let Some(inner) = input.strip_prefix("Some(\"").and_then(|s| s.strip_suffix("\")")) else {
+        return Err(format!("invalid detected title {:?}", input));
+    };
+
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('0') => result.push('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => result.push(c),
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    Some(c) => result.push(c),
+                    None => return Err(format!("invalid unicode escape in detected title {:?}", input)),
+                }
+            },
+            _ => return Err(format!("invalid escape in detected title {:?}", input)),
+        }
+    }
+
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A line as written by the legacy format, with the detected title formatted by `{:?}`.
+    fn legacy_line(song: &Song) -> String {
+        let fields = [
+            song.artist.clone(),
+            song.title.clone(),
+            song.release_year.to_string(),
+            song.youtube_year.to_string(),
+            song.video_id.clone(),
+            song.raw_title.clone(),
+            format!("{:?}", song.detected_title),
+        ];
+        fields.join(&char::from(31).to_string())
+    }
+
+    #[test]
+    fn unescapes_debug_output() {
+        for title in ["Queen - Bohemian Rhapsody", "Sinéad O'Connor - \"Nothing\" \\ Compares", "Tab\tNew line\nReturn\r", "Bell \u{7} Nul \0 Delete \u{7f}", "Zero width \u{200b} and 🎸"] {
+            let input = format!("{:?}", Some(title));
+            assert_eq!(parse_option_string(&input).unwrap().as_deref(), Some(title), "{}", input);
+        }
+        assert_eq!(parse_option_string("None").unwrap(), None);
+        assert!(parse_option_string("Some(\"\\x\")").is_err());
+        assert!(parse_option_string("Some(\"\\u{110000}\")").is_err());
+        assert!(parse_option_string("\"no option\"").is_err());
+    }
+
+    #[test]
+    fn legacy_lists_migrate_to_json() {
+        let songs = [
+            Song { artist: "Queen".into(), title: "Bohemian Rhapsody".into(), release_year: 1975, youtube_year: 2008, video_id: "fJ9rUzIMcZQ".into(),
+                raw_title: "Queen – Bohemian Rhapsody (Official Video)".into(), detected_title: Some("Queen - Bohemian Rhapsody \u{2013} \"Live\"\u{7}".into()), ..Default::default() },
+            Song { artist: "ABBA".into(), title: "Dancing Queen".into(), release_year: 1976, youtube_year: 2009, video_id: "xFrGuyw1V8s".into(),
+                raw_title: "ABBA - Dancing Queen".into(), detected_title: None, ..Default::default() },
+        ];
+        let content = songs.iter().map(legacy_line).collect::<Vec<_>>().join("\n") + "\n\n";
+
+        let path = std::env::temp_dir().join(format!("carnister-legacy-{}.txt", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let legacy = load_song_list(&path).unwrap();
+        let json_path = path.with_extension("json");
+        write_song_list(&json_path, &legacy).unwrap();
+        let migrated = load_song_list(&json_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&json_path).unwrap();
+
+        assert_eq!(migrated.len(), 2);
+        for (song, migrated) in songs.iter().zip(&migrated) {
+            assert_eq!(legacy_line(song), legacy_line(migrated));
+        }
+    }
+
+    #[test]
+    fn reports_every_malformed_legacy_line() {
+        let separator = char::from(31).to_string();
+        let valid = ["A", "T", "1975", "2008", "id", "A - T", "None"].join(&separator);
+        let bad_year = ["A", "T", "19x5", "2008", "id", "A - T", "None"].join(&separator);
+        let content = [valid.as_str(), "too few fields", bad_year.as_str()].join("\n");
+
+        let Err(error) = parse_legacy_song_list(&content) else { panic!("malformed lines were accepted") };
+        assert!(error.starts_with("2 malformed lines"), "{}", error);
+        assert!(error.contains("line 2: expected 7 fields, found 1"), "{}", error);
+        assert!(error.contains("line 3: invalid release year \"19x5\""), "{}", error);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let path = std::env::temp_dir().join(format!("carnister-version-{}.json", std::process::id()));
+        std::fs::write(&path, format!("{{\"version\": {}, \"songs\": []}}", SONG_LIST_VERSION + 1)).unwrap();
+        let result = load_song_list(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
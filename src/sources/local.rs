@@ -0,0 +1,137 @@
+//! Local music folders, read from the tags embedded in the audio files.
+//!
+//! The "playlist" is a directory, searched recursively. Cards link to the files below a base URL,
+//! e.g. the server started by `carnister serve` or any web server hosting the same folder.
+
+use std::{error::Error, path::Path, time::SystemTime};
+use chrono::{DateTime, Datelike, Local};
+use log::*;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::Client;
+use walkdir::WalkDir;
+
+use crate::BoxFuture;
+
+use super::{tags::{read_tags, AUDIO_EXTENSIONS}, youtube::clean_title, SongSource, SourceKind, SourceTrack};
+
+/// Characters left as they are in path segments of playback links
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+pub struct LocalFolder {
+    base_url: String,
+}
+
+impl LocalFolder {
+    /// `base_url` is where the imported folder is served, the relative file paths are appended to it.
+    pub fn new(base_url: &str) -> Self {
+        LocalFolder { base_url: normalize_base_url(base_url) }
+    }
+}
+
+impl SongSource for LocalFolder {
+
+    fn kind(&self) -> SourceKind {
+        SourceKind::Local
+    }
+
+    /// Any directory path.
+    fn parse_playlist_id<'a>(&self, input: &'a str) -> Option<&'a str> {
+        let input = input.trim();
+        (!input.is_empty()).then_some(input)
+    }
+
+    /// Audio files in the directory and its subdirectories, sorted by path.
+    fn fetch_tracks<'a>(&'a self, _client: &'a Client, playlist_id: &'a str) -> BoxFuture<'a, Result<Vec<SourceTrack>, Box<dyn Error>>> {
+        Box::pin(async move {
+
+            let root = Path::new(playlist_id);
+            if !root.is_dir() {
+                return Err(format!("{} is not a directory", root.display()).into());
+            }
+
+            info!("Reading tags from {}...", root.display());
+
+            let mut tracks = Vec::new();
+
+            for entry in WalkDir::new(root).sort_by_file_name() {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        warn!("Skipping {}", e);
+                        continue;
+                    }
+                };
+                if !entry.file_type().is_file() || !is_audio_file(entry.path()) {
+                    continue;
+                }
+                match self.read_track(root, entry.path()) {
+                    Ok(track) => tracks.push(track),
+                    Err(e) => warn!("Skipping {}: {}", entry.path().display(), e),
+                }
+            }
+
+            info!("Found {} audio files", tracks.len());
+
+            Ok(tracks)
+        })
+    }
+}
+
+impl LocalFolder {
+
+    fn read_track(&self, root: &Path, path: &Path) -> Result<SourceTrack, Box<dyn Error>> {
+
+        let tags = read_tags(path)?;
+        let relative = path.strip_prefix(root)?;
+        let file_name = relative.to_string_lossy().to_string();
+
+        let (artist, title) = match (tags.artist, tags.title) {
+            (Some(artist), Some(title)) => (artist, title),
+            _ => artist_and_title_from_file_name(path).ok_or("no artist and title tags")?,
+        };
+
+        let year = match tags.year.or(tags.original_year) {
+            Some(year) => year,
+            None => modification_year(path)?,
+        };
+
+        Ok(SourceTrack {
+            id: playback_link(&self.base_url, relative),
+            raw_title: format!("{} - {}", artist, title),
+            title: clean_title(&title),
+            artist,
+            year,
+            duration: None,
+            isrc: tags.isrc,
+            // only the original year is trusted, the plain year tag is often that of a reissue or compilation
+            release_year: tags.original_year,
+            year_justification: tags.original_year.map(|y| format!("Original year tag {} of {}", y, file_name)),
+        })
+    }
+}
+
+pub(super) fn normalize_base_url(base_url: &str) -> String {
+    format!("{}/", base_url.trim_end_matches('/'))
+}
+
+/// Link to a file below the served folder, `base_url` ends with a slash.
+pub(super) fn playback_link(base_url: &str, relative: &Path) -> String {
+    let url_path: Vec<String> = relative.iter().map(|segment| utf8_percent_encode(&segment.to_string_lossy(), PATH_SEGMENT).to_string()).collect();
+    format!("{}{}", base_url, url_path.join("/"))
+}
+
+/// Untagged files are often named "Artist - Title.mp3".
+pub(super) fn artist_and_title_from_file_name(path: &Path) -> Option<(String, String)> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let (artist, title) = stem.split_once(" - ")?;
+    Some((artist.trim().to_string(), title.trim().to_string()))
+}
+
+pub(super) fn is_audio_file(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+pub(super) fn modification_year(path: &Path) -> Result<i32, Box<dyn Error>> {
+    let modified: SystemTime = path.metadata()?.modified()?;
+    Ok(DateTime::<Local>::from(modified).year())
+}
@@ -0,0 +1,95 @@
+//! Places song lists can be imported from.
+//!
+//! Every source implements [`SongSource`], which turns a playlist into [`SourceTrack`]s
+//! that the import then looks up release years for.
+
+pub mod local;
+pub mod playlist_file;
+pub mod spotify;
+pub mod tags;
+pub mod youtube;
+pub mod youtube_export;
+pub mod youtube_page;
+
+use std::error::Error;
+use clap::ValueEnum;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::BoxFuture;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    #[default]
+    Youtube,
+    Spotify,
+    Local,
+}
+
+impl SourceKind {
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceKind::Youtube => "YouTube",
+            SourceKind::Spotify => "Spotify",
+            SourceKind::Local => "Local",
+        }
+    }
+
+    /// What the year a source states for a track means.
+    pub fn year_description(self) -> &'static str {
+        match self {
+            SourceKind::Youtube => "YouTube upload year",
+            SourceKind::Spotify => "Spotify album release year",
+            SourceKind::Local => "year tag or file date",
+        }
+    }
+
+    /// Link to play the track with the given id.
+    pub fn playback_url(self, id: &str) -> String {
+        match self {
+            SourceKind::Youtube => format!("https://music.youtube.com/watch?v={}", id),
+            SourceKind::Spotify => format!("https://open.spotify.com/track/{}", id),
+            // local tracks are identified by their full playback link
+            SourceKind::Local => id.to_string(),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == SourceKind::default()
+    }
+}
+
+/// A playlist entry before its release year is looked up.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SourceTrack {
+    /// ID of the track at the source, used for the playback link. The link itself for local files.
+    pub id: String,
+    /// Artist and title to query metadata providers with
+    pub artist: String,
+    pub title: String,
+    /// Title as shown by the source
+    pub raw_title: String,
+    /// Year the source states, see [`SourceKind::year_description`]
+    pub year: i32,
+    /// Length in seconds
+    pub duration: Option<u64>,
+    pub isrc: Option<String>,
+    /// Release year known without a lookup, e.g. from an original year tag
+    #[serde(default)]
+    pub release_year: Option<i32>,
+    #[serde(default)]
+    pub year_justification: Option<String>,
+}
+
+pub trait SongSource {
+
+    fn kind(&self) -> SourceKind;
+
+    /// Playlist id from a link or a bare id.
+    fn parse_playlist_id<'a>(&self, input: &'a str) -> Option<&'a str>;
+
+    /// All tracks of the playlist in playlist order.
+    fn fetch_tracks<'a>(&'a self, client: &'a Client, playlist_id: &'a str) -> BoxFuture<'a, Result<Vec<SourceTrack>, Box<dyn Error>>>;
+}
@@ -0,0 +1,318 @@
+//! M3U and XSPF playlists exported by media players.
+//!
+//! Artist and title come from `#EXTINF` lines or XSPF `creator` and `title` elements, falling back to
+//! the tags and the name of the file an entry points to. Entries pointing to web addresses link to
+//! them, local files are linked below a base URL like those of [`super::local::LocalFolder`], with
+//! the playlist's directory as the served folder.
+
+use std::{error::Error, path::{Component, Path, PathBuf}};
+use log::*;
+use percent_encoding::percent_decode_str;
+use reqwest::Client;
+
+use crate::BoxFuture;
+
+use super::{local::{artist_and_title_from_file_name, is_audio_file, modification_year, normalize_base_url, playback_link}, tags::{read_tags, Tags}, youtube::clean_title, SongSource, SourceKind, SourceTrack};
+
+/// File extensions of the supported playlist formats, lowercase.
+pub const PLAYLIST_EXTENSIONS: [&str; 3] = ["m3u", "m3u8", "xspf"];
+
+pub struct PlaylistFile {
+    base_url: String,
+}
+
+/// A playlist entry as written in the file.
+#[derive(Default)]
+struct Entry {
+    location: String,
+    artist: Option<String>,
+    title: Option<String>,
+    /// Length in seconds
+    duration: Option<u64>,
+}
+
+impl PlaylistFile {
+    /// `base_url` is where the playlist's directory is served.
+    pub fn new(base_url: &str) -> Self {
+        PlaylistFile { base_url: normalize_base_url(base_url) }
+    }
+
+    pub fn is_playlist_file(path: &Path) -> bool {
+        path.extension().and_then(|e| e.to_str()).is_some_and(|e| PLAYLIST_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+    }
+}
+
+impl SongSource for PlaylistFile {
+
+    fn kind(&self) -> SourceKind {
+        SourceKind::Local
+    }
+
+    /// Path of the playlist file.
+    fn parse_playlist_id<'a>(&self, input: &'a str) -> Option<&'a str> {
+        let input = input.trim();
+        PlaylistFile::is_playlist_file(Path::new(input)).then_some(input)
+    }
+
+    fn fetch_tracks<'a>(&'a self, _client: &'a Client, playlist_id: &'a str) -> BoxFuture<'a, Result<Vec<SourceTrack>, Box<dyn Error>>> {
+        Box::pin(async move {
+
+            let path = Path::new(playlist_id);
+            info!("Reading playlist {}...", path.display());
+
+            let entries = parse_entries(path, &std::fs::read(path)?)?;
+
+            let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let playlist_year = modification_year(path)?;
+
+            let mut tracks = Vec::new();
+            for entry in entries {
+                match self.entry_to_track(dir, playlist_year, entry) {
+                    Ok(track) => tracks.push(track),
+                    Err((location, e)) => warn!("Skipping {}: {}", location, e),
+                }
+            }
+
+            info!("Found {} playlist entries", tracks.len());
+
+            Ok(tracks)
+        })
+    }
+}
+
+impl PlaylistFile {
+
+    /// `playlist_year` stands in for the source year of entries that aren't local files.
+    fn entry_to_track(&self, dir: &Path, playlist_year: i32, entry: Entry) -> Result<SourceTrack, (String, Box<dyn Error>)> {
+
+        let file = local_path(dir, &entry.location);
+
+        let tags = match &file {
+            Some(file) if file.is_file() && is_audio_file(file) => read_tags(file).unwrap_or_else(|e| {
+                warn!("Could not read the tags of {}: {}", file.display(), e);
+                Tags::default()
+            }),
+            _ => Tags::default(),
+        };
+
+        let (artist, title) = match (entry.artist.or(tags.artist), entry.title.or(tags.title)) {
+            (Some(artist), Some(title)) => (artist, title),
+            _ => file.as_deref().and_then(artist_and_title_from_file_name).ok_or((entry.location.clone(), "no artist and title".into()))?,
+        };
+
+        let year = tags.year.or(tags.original_year)
+            .or_else(|| file.as_deref().and_then(|f| modification_year(f).ok()))
+            .unwrap_or(playlist_year);
+
+        let id = match &file {
+            None => entry.location.clone(),
+            Some(file) => match relative_to(dir, file) {
+                Some(relative) => playback_link(&self.base_url, &relative),
+                None => {
+                    warn!("{} is outside of the playlist's folder, its card can't be played", file.display());
+                    entry.location.clone()
+                }
+            },
+        };
+
+        Ok(SourceTrack {
+            id,
+            raw_title: format!("{} - {}", artist, title),
+            title: clean_title(&title),
+            artist,
+            year,
+            duration: entry.duration,
+            isrc: tags.isrc,
+            release_year: tags.original_year,
+            year_justification: tags.original_year.zip(file).map(|(y, file)| format!("Original year tag {} of {}", y, file.display())),
+        })
+    }
+}
+
+/// Entries of the playlist file at `path` with the content `bytes`.
+fn parse_entries(path: &Path, bytes: &[u8]) -> Result<Vec<Entry>, Box<dyn Error>> {
+
+    // older M3U files are often not UTF-8
+    let content = String::from_utf8_lossy(bytes);
+    let content = content.trim_start_matches('\u{feff}');
+
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("xspf")) {
+        parse_xspf(content)
+    } else {
+        Ok(parse_m3u(content))
+    }
+}
+
+/// Entries of an extended M3U playlist. Plain M3U files only list locations.
+fn parse_m3u(content: &str) -> Vec<Entry> {
+
+    let mut entries = Vec::new();
+    let mut info = Entry::default();
+
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            // "#EXTINF:215,Artist - Title", players may add attributes after the duration, whose
+            // quoted values can contain commas
+            let mut quoted = false;
+            let comma = extinf.find(|c| {
+                quoted ^= c == '"';
+                c == ',' && !quoted
+            });
+            let (properties, display) = comma.map_or((extinf, ""), |i| (&extinf[..i], &extinf[i + 1..]));
+            let duration = properties.split_whitespace().next().and_then(|d| d.parse::<f64>().ok());
+            info.duration = duration.filter(|d| *d > 0.0).map(|d| d.round() as u64);
+            match display.split_once(" - ") {
+                Some((artist, title)) => {
+                    info.artist = Some(artist.trim().to_string());
+                    info.title = Some(title.trim().to_string());
+                },
+                None if !display.trim().is_empty() => info.title = Some(display.trim().to_string()),
+                None => (),
+            }
+        } else if !line.starts_with('#') {
+            entries.push(Entry { location: line.to_string(), ..std::mem::take(&mut info) });
+        }
+    }
+
+    entries
+}
+
+fn parse_xspf(content: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+
+    let document = roxmltree::Document::parse(content)?;
+
+    let entries = document.descendants().filter(|n| n.has_tag_name("track")).filter_map(|track| {
+        let text = |name: &str| track.children()
+            .find(|c| c.tag_name().name() == name)
+            .and_then(|c| c.text())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+        let Some(location) = text("location") else {
+            warn!("Skipping {}, it has no location", text("title").as_deref().unwrap_or("a track"));
+            return None;
+        };
+        Some(Entry {
+            // locations are URIs, relative ones are only decoded here and file:// ones in local_path
+            location: match location.contains("://") {
+                true => location,
+                false => percent_decode_str(&location).decode_utf8_lossy().into_owned(),
+            },
+            artist: text("creator"),
+            title: text("title"),
+            // milliseconds
+            duration: text("duration").and_then(|d| d.parse::<u64>().ok()).map(|ms| ms / 1000),
+        })
+    }).collect();
+
+    Ok(entries)
+}
+
+/// The file a location points to, `None` for web addresses.
+fn local_path(dir: &Path, location: &str) -> Option<PathBuf> {
+    if location.is_empty() || location.starts_with("http://") || location.starts_with("https://") {
+        return None;
+    }
+    let path = match location.strip_prefix("file://") {
+        Some(uri) => PathBuf::from(percent_decode_str(uri.trim_start_matches("localhost")).decode_utf8_lossy().as_ref()),
+        None => PathBuf::from(location),
+    };
+    Some(dir.join(path))
+}
+
+fn relative_to(dir: &Path, file: &Path) -> Option<PathBuf> {
+    // compare resolved paths if the file exists, so ".." and symbolic links don't matter
+    let relative = match (dir.canonicalize(), file.canonicalize()) {
+        (Ok(dir), Ok(file)) => file.strip_prefix(dir).ok()?.to_path_buf(),
+        _ => file.strip_prefix(dir).ok()?.to_path_buf(),
+    };
+    (!relative.components().any(|c| matches!(c, Component::ParentDir))).then_some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Summary<'a> = (&'a str, Option<&'a str>, Option<&'a str>, Option<u64>);
+
+    fn summary(entries: &[Entry]) -> Vec<Summary<'_>> {
+        entries.iter().map(|e| (e.location.as_str(), e.artist.as_deref(), e.title.as_deref(), e.duration)).collect()
+    }
+
+    #[test]
+    fn reads_extended_m3u() {
+        let content = "\u{feff}#EXTM3U\r\n\
+            #EXTINF:215,Kate Bush - Running Up That Hill\r\n\
+            Kate Bush/Running Up That Hill.flac\r\n\
+            \r\n\
+            #EXTINF:-1 tvg-name=\"Radio, Live\" group-title=\"Music\",Radio Stream\r\n\
+            https://radio.example.com/stream\r\n\
+            # a comment\r\n\
+            /music/Queen - Bohemian Rhapsody.mp3\r\n";
+        let entries = parse_entries(Path::new("list.M3U8"), content.as_bytes()).unwrap();
+        assert_eq!(summary(&entries), [
+            ("Kate Bush/Running Up That Hill.flac", Some("Kate Bush"), Some("Running Up That Hill"), Some(215)),
+            ("https://radio.example.com/stream", None, Some("Radio Stream"), None),
+            ("/music/Queen - Bohemian Rhapsody.mp3", None, None, None),
+        ]);
+    }
+
+    #[test]
+    fn reads_xspf() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <trackList>
+                <track>
+                  <location>Kate%20Bush/Running%20Up%20That%20Hill.flac</location>
+                  <creator>Kate Bush</creator>
+                  <title>Running Up That Hill</title>
+                  <duration>298000</duration>
+                </track>
+                <track>
+                  <title>Nowhere</title>
+                  <location> </location>
+                </track>
+                <track>
+                  <location>file:///music/Queen%20-%20Bohemian%20Rhapsody.mp3</location>
+                </track>
+              </trackList>
+            </playlist>"#;
+        let entries = parse_entries(Path::new("list.xspf"), format!("\u{feff}{}", content).as_bytes()).unwrap();
+        assert_eq!(summary(&entries), [
+            ("Kate Bush/Running Up That Hill.flac", Some("Kate Bush"), Some("Running Up That Hill"), Some(298)),
+            ("file:///music/Queen%20-%20Bohemian%20Rhapsody.mp3", None, None, None),
+        ]);
+        assert!(parse_entries(Path::new("list.xspf"), b"<playlist>").is_err());
+    }
+
+    #[test]
+    fn resolves_locations() {
+        let dir = Path::new("/music/lists");
+        assert_eq!(local_path(dir, "https://radio.example.com/stream"), None);
+        assert_eq!(local_path(dir, ""), None);
+        assert_eq!(local_path(dir, "Kate Bush/Hill.flac"), Some(PathBuf::from("/music/lists/Kate Bush/Hill.flac")));
+        assert_eq!(local_path(dir, "/music/Hill.flac"), Some(PathBuf::from("/music/Hill.flac")));
+        assert_eq!(local_path(dir, "file:///music/Kate%20Bush.flac"), Some(PathBuf::from("/music/Kate Bush.flac")));
+        assert_eq!(local_path(dir, "file://localhost/music/Kate%20Bush.flac"), Some(PathBuf::from("/music/Kate Bush.flac")));
+    }
+
+    #[test]
+    fn links_only_files_inside_the_folder() {
+        let dir = Path::new("/nonexistent/music/lists");
+        assert_eq!(relative_to(dir, &dir.join("Kate Bush/Hill.flac")), Some(PathBuf::from("Kate Bush/Hill.flac")));
+        assert_eq!(relative_to(dir, &dir.join("../Hill.flac")), None);
+        assert_eq!(relative_to(dir, Path::new("/nonexistent/music/Hill.flac")), None);
+
+        // existing files are compared by their resolved paths
+        let root = std::env::temp_dir().join(format!("carnister-playlist-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("lists/songs")).unwrap();
+        std::fs::write(root.join("lists/songs/Hill.flac"), b"").unwrap();
+        std::fs::write(root.join("Outside.flac"), b"").unwrap();
+        let lists = root.join("lists");
+        let inside = relative_to(&lists, &lists.join("songs/../songs/Hill.flac"));
+        let outside = relative_to(&lists, &lists.join("songs/../../Outside.flac"));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(inside, Some(PathBuf::from("songs/Hill.flac")));
+        assert_eq!(outside, None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Spotify playlists via the Web API.
+//!
+//! Authenticates with the client credentials flow, which is enough for public playlists. Create an
+//! app at <https://developer.spotify.com/dashboard> and put `client_id:client_secret` into
+//! spotify_credentials.txt in the data directory, or configure them, see [`crate::config`].
+
+use std::error::Error;
+use log::*;
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::{config, http::{post_form, receive_json_authorized}, BoxFuture};
+
+use super::{youtube::clean_title, SongSource, SourceKind, SourceTrack};
+
+pub const CREDENTIALS_FILE: &str = "spotify_credentials.txt";
+pub const API_URL: &str = "https://api.spotify.com";
+pub const ACCOUNTS_URL: &str = "https://accounts.spotify.com";
+
+/// Suffixes like " - Remastered 2011" that name a version of the same recording
+const VERSION_SUFFIXES: [&str; 3] = ["remaster", "mono", "stereo"];
+
+pub struct Spotify {
+    client_id: String,
+    client_secret: String,
+    api_url: String,
+    accounts_url: String,
+}
+
+impl Spotify {
+
+    /// `api_url` and `accounts_url` replace [`API_URL`] and [`ACCOUNTS_URL`], e.g. to test against a mock server.
+    pub fn new(api_url: &str, accounts_url: &str) -> Result<Self, Box<dyn Error>> {
+        let config = config::get();
+        let Some((client_id, client_secret)) = &config.spotify_credentials else {
+            return Err(format!("No Spotify credentials specified. Put client_id:client_secret of your Spotify app in the {} file or set CARNISTER_SPOTIFY_CLIENT_ID and CARNISTER_SPOTIFY_CLIENT_SECRET.", config.data_dir.join(CREDENTIALS_FILE).display()).into());
+        };
+        Ok(Spotify {
+            client_id: client_id.trim().to_string(),
+            client_secret: client_secret.trim().to_string(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            accounts_url: accounts_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    async fn access_token(&self, client: &Client) -> Result<String, Box<dyn Error>> {
+        let json = post_form(client, &format!("{}/api/token", self.accounts_url), Some((&self.client_id, &self.client_secret)), &[("grant_type", "client_credentials")])
+            .await
+            .map_err(|e| format!("Spotify authentication failed: {}", e))?;
+        json["access_token"].as_str().map(str::to_string).ok_or_else(|| "Spotify returned no access token".into())
+    }
+}
+
+impl SongSource for Spotify {
+
+    fn kind(&self) -> SourceKind {
+        SourceKind::Spotify
+    }
+
+    /// Accepts open.spotify.com links, `spotify:playlist:` URIs and bare ids.
+    fn parse_playlist_id<'a>(&self, input: &'a str) -> Option<&'a str> {
+        let input = input.trim();
+        let id = match input.split_once("playlist/").or(input.split_once("playlist:")) {
+            Some((_, id)) => id.split(['?', '/', '#']).next()?,
+            None => input,
+        };
+        (!id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())).then_some(id)
+    }
+
+    fn fetch_tracks<'a>(&'a self, client: &'a Client, playlist_id: &'a str) -> BoxFuture<'a, Result<Vec<SourceTrack>, Box<dyn Error>>> {
+        Box::pin(async move {
+
+            let token = self.access_token(client).await?;
+
+            info!("Fetching tracks from playlist...");
+
+            let mut tracks = Vec::new();
+            let mut url = Some(format!(
+                "{}/v1/playlists/{}/tracks?limit=100&additional_types=track&fields=next,items(track(id,name,type,is_local,duration_ms,external_ids(isrc),artists(name),album(release_date)))",
+                self.api_url, playlist_id
+            ));
+
+            while let Some(page_url) = url {
+                let json = receive_json_authorized(client, &page_url, &token).await.map_err(|e| format!("Error while fetching tracks: {}", e))?;
+                tracks.extend(json["items"].as_array().into_iter().flatten().filter_map(|item| parse_track(&item["track"])));
+                url = json["next"].as_str().map(str::to_string);
+            }
+
+            Ok(tracks)
+        })
+    }
+}
+
+/// `None` for podcast episodes, local files and removed tracks.
+fn parse_track(track: &Value) -> Option<SourceTrack> {
+
+    if track.is_null() {
+        return None;
+    }
+
+    let name = track["name"].as_str().unwrap_or("");
+
+    if track["type"].as_str() != Some("track") || track["is_local"].as_bool() == Some(true) {
+        warn!("Skipping {}, only Spotify tracks can be imported", name);
+        return None;
+    }
+
+    let id = track["id"].as_str()?.to_string();
+
+    // release dates are "1981-12-01", "1981-12" or "1981" depending on their precision
+    let Some(year) = track["album"]["release_date"].as_str().and_then(|d| d.split('-').next()).and_then(|y| y.parse::<i32>().ok()) else {
+        warn!("Skipping {}, its album has no release date", name);
+        return None;
+    };
+
+    let artists: Vec<&str> = track["artists"].as_array().into_iter().flatten().filter_map(|a| a["name"].as_str()).collect();
+
+    let title = match name.rsplit_once(" - ") {
+        Some((title, suffix)) if VERSION_SUFFIXES.iter().any(|s| suffix.to_lowercase().contains(s)) => title,
+        _ => name,
+    };
+
+    Some(SourceTrack {
+        id,
+        // the first credited artist gives the best text query results
+        artist: artists.first().copied().unwrap_or("").to_string(),
+        title: clean_title(title),
+        raw_title: format!("{} - {}", artists.join(", "), name),
+        year,
+        duration: track["duration_ms"].as_u64().map(|ms| ms / 1000),
+        isrc: track["external_ids"]["isrc"].as_str().map(str::to_string),
+        release_year: None,
+        year_justification: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use serde_json::json;
+    use tiny_http::{Header, Response, Server};
+    use super::*;
+
+    /// Answers the token request and two playlist pages like the Spotify API, checking the credentials.
+    fn mock_spotify() -> String {
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let next_page = format!("{}/v1/playlists/abc/tracks?offset=100", base_url);
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let authorization = request.headers().iter().find(|h| h.field.equiv("Authorization")).map(|h| h.value.to_string());
+                let (expected_authorization, body) = match request.url() {
+                    // base64 of "id:secret"
+                    "/api/token" => ("Basic aWQ6c2VjcmV0", json!({ "access_token": "token", "token_type": "Bearer" })),
+                    "/v1/playlists/abc/tracks?offset=100" => ("Bearer token", json!({
+                        "next": null,
+                        "items": [
+                            { "track": { "id": "2", "name": "Heroes - 2017 Remaster", "type": "track", "is_local": false, "duration_ms": 371000,
+                                "artists": [{ "name": "David Bowie" }], "album": { "release_date": "1977" } } },
+                        ],
+                    })),
+                    url if url.starts_with("/v1/playlists/abc/tracks?") => ("Bearer token", json!({
+                        "next": next_page,
+                        "items": [
+                            { "track": { "id": "1", "name": "Under Pressure", "type": "track", "is_local": false, "duration_ms": 248000,
+                                "external_ids": { "isrc": "GBUM71029604" },
+                                "artists": [{ "name": "Queen" }, { "name": "David Bowie" }], "album": { "release_date": "1981-10-26" } } },
+                            { "track": { "id": null, "name": "My Recording", "type": "track", "is_local": true, "artists": [], "album": {} } },
+                            { "track": { "id": "3", "name": "A Podcast", "type": "episode" } },
+                            { "track": null },
+                        ],
+                    })),
+                    _ => ("", json!({ "error": "not found" })),
+                };
+                let response = match authorization.as_deref() == Some(expected_authorization) {
+                    true => Response::from_string(body.to_string()),
+                    false => Response::from_string(json!({ "error": "unauthorized" }).to_string()).with_status_code(401),
+                };
+                let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+                request.respond(response.with_header(header)).unwrap();
+            }
+        });
+
+        base_url
+    }
+
+    #[tokio::test]
+    async fn imports_all_pages() {
+
+        let base_url = mock_spotify();
+        let spotify = Spotify { client_id: "id".into(), client_secret: "secret".into(), api_url: base_url.clone(), accounts_url: base_url };
+
+        let playlist_id = spotify.parse_playlist_id("https://open.spotify.com/playlist/abc?si=123").unwrap();
+        let tracks = spotify.fetch_tracks(&Client::new(), playlist_id).await.unwrap();
+
+        assert_eq!(tracks.len(), 2);
+
+        assert_eq!(tracks[0].id, "1");
+        assert_eq!(tracks[0].artist, "Queen");
+        assert_eq!(tracks[0].title, "Under Pressure");
+        assert_eq!(tracks[0].raw_title, "Queen, David Bowie - Under Pressure");
+        assert_eq!(tracks[0].year, 1981);
+        assert_eq!(tracks[0].duration, Some(248));
+        assert_eq!(tracks[0].isrc.as_deref(), Some("GBUM71029604"));
+
+        assert_eq!(tracks[1].artist, "David Bowie");
+        assert_eq!(tracks[1].title, "Heroes");
+        assert_eq!(tracks[1].year, 1977);
+        assert_eq!(tracks[1].isrc, None);
+    }
+}
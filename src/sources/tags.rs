@@ -0,0 +1,391 @@
+//! Reads artist, title, year and ISRC from the tags embedded in audio files: ID3 in MP3, WAV and
+//! AIFF files, Vorbis comments in FLAC, Ogg Vorbis and Opus files and iTunes metadata in MP4 files.
+
+use std::{error::Error, fs::File, io::{Read, Seek, SeekFrom}, path::Path};
+use id3::TagLike;
+
+/// File extensions [`read_tags`] understands, lowercase.
+pub const AUDIO_EXTENSIONS: [&str; 10] = ["mp3", "wav", "aif", "aiff", "flac", "ogg", "oga", "opus", "m4a", "mp4"];
+
+/// Ogg files are only searched this far for the comment header.
+const OGG_SEARCH_LIMIT: u64 = 1024 * 1024;
+
+#[derive(Default)]
+pub struct Tags {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    /// Year of this release, which can be a reissue
+    pub year: Option<i32>,
+    /// Year of the first release, as written by taggers like MusicBrainz Picard
+    pub original_year: Option<i32>,
+    pub isrc: Option<String>,
+}
+
+impl Tags {
+    /// Stores a Vorbis comment or MP4 freeform field. Keys are case-insensitive.
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        match key.to_uppercase().as_str() {
+            "ARTIST" => set_once(&mut self.artist, value.to_string()),
+            "TITLE" => set_once(&mut self.title, value.to_string()),
+            "DATE" | "YEAR" => set_once_opt(&mut self.year, parse_year(value)),
+            "ORIGINALDATE" | "ORIGINALYEAR" => set_once_opt(&mut self.original_year, parse_year(value)),
+            "ISRC" => set_once(&mut self.isrc, value.to_string()),
+            _ => (),
+        }
+    }
+}
+
+/// Keeps the first of repeated fields, e.g. the main artist if every artist has its own field.
+fn set_once<T>(field: &mut Option<T>, value: T) {
+    if field.is_none() {
+        *field = Some(value);
+    }
+}
+
+fn set_once_opt<T>(field: &mut Option<T>, value: Option<T>) {
+    if let Some(value) = value {
+        set_once(field, value);
+    }
+}
+
+/// Dates in tags are "1981-12-01", "1981" or similar.
+fn parse_year(date: &str) -> Option<i32> {
+    date.trim().get(..4)?.parse::<i32>().ok().filter(|year| *year > 0)
+}
+
+/// Tags of a file with one of the [`AUDIO_EXTENSIONS`], empty if it has none.
+pub fn read_tags(path: &Path) -> Result<Tags, Box<dyn Error>> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "mp3" | "wav" | "aif" | "aiff" => read_id3(path),
+        "flac" => read_flac(path),
+        "ogg" | "oga" | "opus" => read_ogg(path),
+        "m4a" | "mp4" => read_mp4(path),
+        _ => Err(format!("Unsupported file type: {}", path.display()).into()),
+    }
+}
+
+fn read_id3(path: &Path) -> Result<Tags, Box<dyn Error>> {
+
+    // keep whatever could be read from partially broken tags
+    let tag = match id3::partial_tag_ok(id3::Tag::read_from_path(path)) {
+        Ok(tag) => tag,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => return Ok(Tags::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let text = |id: &str| tag.get(id).and_then(|frame| frame.content().text()).map(str::to_string);
+
+    Ok(Tags {
+        // multiple artists are separated by null characters in ID3v2.4
+        artist: tag.artist().and_then(|a| a.split('\0').next()).map(str::to_string),
+        title: tag.title().map(str::to_string),
+        // TDRC is the recording time in ID3v2.4, TYER the year in ID3v2.3
+        year: text("TDRC").or(text("TYER")).as_deref().and_then(parse_year),
+        // TDOR is the original release time in ID3v2.4, TORY the original release year in ID3v2.3
+        original_year: text("TDOR").or(text("TORY")).as_deref().and_then(parse_year),
+        isrc: text("TSRC"),
+    })
+}
+
+fn read_flac(path: &Path) -> Result<Tags, Box<dyn Error>> {
+
+    let mut file = File::open(path)?;
+    let file_length = file.metadata()?.len();
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(format!("{} is not a FLAC file", path.display()).into());
+    }
+
+    let mut tags = Tags::default();
+
+    // metadata blocks start with a byte for the last-block flag and block type and a 24 bit length
+    loop {
+        let mut header = [0; 4];
+        file.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        if length as u64 > file_length - file.stream_position()? {
+            return Err(format!("{} has a malformed metadata block", path.display()).into());
+        }
+
+        if header[0] & 0x7f == 4 {
+            let mut block = vec![0; length];
+            file.read_exact(&mut block)?;
+            parse_vorbis_comments(&block, &mut tags);
+            break;
+        }
+        if is_last {
+            break;
+        }
+        file.seek(SeekFrom::Current(length as i64))?;
+    }
+
+    Ok(tags)
+}
+
+fn read_ogg(path: &Path) -> Result<Tags, Box<dyn Error>> {
+
+    let mut data = Vec::new();
+    File::open(path)?.take(OGG_SEARCH_LIMIT).read_to_end(&mut data)?;
+
+    // concatenate the page payloads, the comment header is the second packet and can span pages
+    let mut packets = Vec::new();
+    let mut position = 0;
+    while data.get(position..position + 4) == Some(b"OggS") {
+        let Some(&segment_count) = data.get(position + 26) else { break };
+        let segments_start = position + 27;
+        let Some(segments) = data.get(segments_start..segments_start + segment_count as usize) else { break };
+        let payload_start = segments_start + segment_count as usize;
+        let payload_end = (payload_start + segments.iter().map(|s| *s as usize).sum::<usize>()).min(data.len());
+        packets.extend_from_slice(&data[payload_start..payload_end]);
+        position = payload_end;
+    }
+
+    let mut tags = Tags::default();
+    for magic in [&b"\x03vorbis"[..], &b"OpusTags"[..]] {
+        if let Some(start) = packets.windows(magic.len()).position(|w| w == magic) {
+            parse_vorbis_comments(&packets[start + magic.len()..], &mut tags);
+            break;
+        }
+    }
+    Ok(tags)
+}
+
+/// Parses a Vorbis comment block, stopping early if it is truncated.
+fn parse_vorbis_comments(block: &[u8], tags: &mut Tags) {
+
+    let mut position = 0;
+    let read_u32 = |position: &mut usize| -> Option<usize> {
+        let bytes = block.get(*position..*position + 4)?;
+        *position += 4;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+
+    // the vendor string comes first
+    let Some(vendor_length) = read_u32(&mut position) else { return };
+    position += vendor_length;
+
+    let Some(count) = read_u32(&mut position) else { return };
+    for _ in 0..count {
+        let Some(length) = read_u32(&mut position) else { return };
+        let Some(comment) = block.get(position..position + length) else { return };
+        position += length;
+        if let Some((key, value)) = String::from_utf8_lossy(comment).split_once('=') {
+            tags.set(key, value);
+        }
+    }
+}
+
+fn read_mp4(path: &Path) -> Result<Tags, Box<dyn Error>> {
+
+    let mut file = File::open(path)?;
+    let file_length = file.metadata()?.len();
+
+    // the moov atom can be at the start or the end, skip over the media data until it is found
+    let mut position = 0;
+    let moov = loop {
+        if position + 8 > file_length {
+            return Ok(Tags::default());
+        }
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        let mut length = u32::from_be_bytes(header[..4].try_into().unwrap()) as u64;
+        let mut header_length = 8;
+        if length == 1 {
+            let mut large = [0; 8];
+            file.read_exact(&mut large)?;
+            length = u64::from_be_bytes(large);
+            header_length = 16;
+        } else if length == 0 {
+            length = file_length - position;
+        }
+        // checked before allocating, a broken length could ask for gigabytes
+        if length < header_length || length > file_length - position {
+            return Err(format!("{} has a malformed atom", path.display()).into());
+        }
+        if &header[4..] == b"moov" {
+            let mut moov = vec![0; (length - header_length) as usize];
+            file.read_exact(&mut moov)?;
+            break moov;
+        }
+        position += length;
+    };
+
+    let mut tags = Tags::default();
+
+    // meta is a full atom with 4 bytes of version and flags before its children
+    let Some(ilst) = find_atom(&moov, b"udta")
+        .and_then(|udta| find_atom(udta, b"meta"))
+        .and_then(|meta| find_atom(meta.get(4..)?, b"ilst"))
+    else {
+        return Ok(tags);
+    };
+
+    for (name, item) in atoms(ilst) {
+        match name {
+            b"\xa9ART" => set_once_opt(&mut tags.artist, mp4_text(item)),
+            b"\xa9nam" => set_once_opt(&mut tags.title, mp4_text(item)),
+            b"\xa9day" => set_once_opt(&mut tags.year, mp4_text(item).as_deref().and_then(parse_year)),
+            // freeform items like ISRC and ORIGINALDATE are named by a name atom
+            b"----" => {
+                let key = find_atom(item, b"name").and_then(|n| n.get(4..)).map(String::from_utf8_lossy);
+                if let (Some(key), Some(value)) = (key, mp4_text(item)) {
+                    tags.set(&key, &value);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    Ok(tags)
+}
+
+/// Name and content of the atoms directly inside `data`.
+fn atoms(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut position = 0;
+    std::iter::from_fn(move || {
+        let header = data.get(position..position + 8)?;
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let content = data.get(position + 8..position + length.max(8))?;
+        position += length.max(8);
+        Some((&header[4..], content))
+    })
+}
+
+fn find_atom<'a>(data: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    atoms(data).find(|(n, _)| *n == name).map(|(_, content)| content)
+}
+
+/// Text of an item's data atom, which starts with 4 bytes of type and 4 bytes of locale.
+fn mp4_text(item: &[u8]) -> Option<String> {
+    let data = find_atom(item, b"data")?.get(8..)?;
+    Some(String::from_utf8_lossy(data).trim().to_string()).filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `data` to a temporary file with the extension and reads its tags.
+    fn read(name: &str, extension: &str, data: &[u8]) -> Result<Tags, Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!("carnister-tags-{}-{}.{}", std::process::id(), name, extension));
+        std::fs::write(&path, data).unwrap();
+        let tags = read_tags(&path);
+        std::fs::remove_file(&path).unwrap();
+        tags
+    }
+
+    fn vorbis_comments(comments: &[&str]) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend(6u32.to_le_bytes());
+        block.extend(b"vendor");
+        block.extend((comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            block.extend((comment.len() as u32).to_le_bytes());
+            block.extend(comment.as_bytes());
+        }
+        block
+    }
+
+    const COMMENTS: [&str; 5] = ["ARTIST=Kate Bush", "artist=Other", "TITLE=Running Up That Hill", "DATE=2018-11-16", "ORIGINALDATE=1985"];
+
+    fn assert_kate_bush(tags: &Tags) {
+        assert_eq!(tags.artist.as_deref(), Some("Kate Bush"));
+        assert_eq!(tags.title.as_deref(), Some("Running Up That Hill"));
+        assert_eq!(tags.year, Some(2018));
+        assert_eq!(tags.original_year, Some(1985));
+    }
+
+    fn flac(comment_block: &[u8], comment_length: usize) -> Vec<u8> {
+        let mut data = b"fLaC".to_vec();
+        // STREAMINFO
+        data.extend([0, 0, 0, 34]);
+        data.extend([0; 34]);
+        let length = (comment_length as u32).to_be_bytes();
+        data.extend([0x84, length[1], length[2], length[3]]);
+        data.extend(comment_block);
+        data
+    }
+
+    #[test]
+    fn reads_flac() {
+        let block = vorbis_comments(&COMMENTS);
+        assert_kate_bush(&read("flac", "flac", &flac(&block, block.len())).unwrap());
+    }
+
+    #[test]
+    fn rejects_flac_blocks_longer_than_the_file() {
+        let block = vorbis_comments(&COMMENTS);
+        assert!(read("long-flac", "flac", &flac(&block, 0xffffff)).is_err());
+    }
+
+    fn ogg_page(packet: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.extend([0; 22]);
+        let mut segments = vec![255; packet.len() / 255];
+        segments.push((packet.len() % 255) as u8);
+        page.push(segments.len() as u8);
+        page.extend(segments);
+        page.extend(packet);
+        page
+    }
+
+    #[test]
+    fn reads_ogg_vorbis_and_opus() {
+        let mut vorbis = ogg_page(b"\x01vorbis identification");
+        vorbis.extend(ogg_page(&[&b"\x03vorbis"[..], &vorbis_comments(&COMMENTS)].concat()));
+        assert_kate_bush(&read("vorbis", "ogg", &vorbis).unwrap());
+
+        // comments longer than a segment
+        let long_title = format!("TITLE={}", "la ".repeat(100));
+        let mut opus = ogg_page(b"OpusHead");
+        opus.extend(ogg_page(&[&b"OpusTags"[..], &vorbis_comments(&["ARTIST=Kate Bush", &long_title])].concat()));
+        let tags = read("opus", "opus", &opus).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Kate Bush"));
+        assert_eq!(tags.title.as_deref(), Some("la ".repeat(100).trim()));
+    }
+
+    fn atom(name: &[u8], content: &[u8]) -> Vec<u8> {
+        [&((content.len() + 8) as u32).to_be_bytes()[..], name, content].concat()
+    }
+
+    fn mp4_item(name: &[u8], text: &str) -> Vec<u8> {
+        atom(name, &atom(b"data", &[&[0, 0, 0, 1, 0, 0, 0, 0][..], text.as_bytes()].concat()))
+    }
+
+    fn mp4(moov: &[u8]) -> Vec<u8> {
+        [atom(b"ftyp", b"M4A "), atom(b"mdat", &[0; 100]), moov.to_vec()].concat()
+    }
+
+    #[test]
+    fn reads_mp4() {
+        let isrc = atom(b"----", &[
+            atom(b"mean", b"\0\0\0\0com.apple.iTunes"),
+            atom(b"name", b"\0\0\0\0ISRC"),
+            atom(b"data", b"\0\0\0\x01\0\0\0\0GBAYE8500001"),
+        ].concat());
+        let ilst = atom(b"ilst", &[mp4_item(b"\xa9ART", "Kate Bush"), mp4_item(b"\xa9nam", "Running Up That Hill"), mp4_item(b"\xa9day", "1985-08-05"), isrc].concat());
+        let meta = atom(b"meta", &[&[0, 0, 0, 0][..], &atom(b"hdlr", &[0; 25]), &ilst].concat());
+        let moov = atom(b"moov", &atom(b"udta", &meta));
+
+        let tags = read("mp4", "m4a", &mp4(&moov)).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Kate Bush"));
+        assert_eq!(tags.title.as_deref(), Some("Running Up That Hill"));
+        assert_eq!(tags.year, Some(1985));
+        assert_eq!(tags.isrc.as_deref(), Some("GBAYE8500001"));
+    }
+
+    #[test]
+    fn rejects_mp4_atoms_longer_than_the_file() {
+        // 64 bit size of about 1 EB
+        let moov = [&1u32.to_be_bytes()[..], b"moov", &(1u64 << 60).to_be_bytes(), &[0; 16]].concat();
+        assert!(read("long-mp4", "m4a", &mp4(&moov)).is_err());
+    }
+}
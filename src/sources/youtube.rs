@@ -0,0 +1,225 @@
+//! YouTube playlists via the Data API, or without an API key from the public playlist page or an
+//! exported playlist file.
+
+use std::{error::Error, path::Path};
+use log::*;
+use regex::Regex;
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::{http::receive_json, BoxFuture};
+
+use super::{youtube_export::read_exported_videos, youtube_page::fetch_videos_from_page, SongSource, SourceKind, SourceTrack};
+
+/// Holds the API key in the data directory, unless it is configured otherwise
+pub const API_KEY_FILE: &str = "youtube_api_key.txt";
+
+pub struct YouTube {
+    /// Without a key, playlists are read from their public page
+    api_key: Option<String>,
+}
+
+impl YouTube {
+    /// Usually [`Config::youtube_api_key`](crate::config::Config::youtube_api_key).
+    pub fn new(api_key: Option<String>) -> Self {
+        YouTube { api_key }
+    }
+}
+
+impl SongSource for YouTube {
+
+    fn kind(&self) -> SourceKind {
+        SourceKind::Youtube
+    }
+
+    fn parse_playlist_id<'a>(&self, input: &'a str) -> Option<&'a str> {
+        parse_playlist_id(input)
+    }
+
+    /// `playlist_id` can also be the path of an exported playlist, see [`is_export_file`].
+    fn fetch_tracks<'a>(&'a self, client: &'a Client, playlist_id: &'a str) -> BoxFuture<'a, Result<Vec<SourceTrack>, Box<dyn Error>>> {
+        Box::pin(async move {
+
+            let videos = if is_export_file(playlist_id) {
+                info!("Reading videos from {}...", playlist_id);
+                read_exported_videos(Path::new(playlist_id))?
+            } else if let Some(api_key) = &self.api_key {
+                info!("Fetching videos from playlist...");
+                let mut videos = fetch_videos(client, api_key, playlist_id).await.map_err(|e| format!("Error while fetching videos: {}", e))?;
+                if let Err(e) = fetch_durations(client, api_key, &mut videos).await {
+                    warn!("Could not fetch video durations, matching without them: {}", e);
+                }
+                videos
+            } else {
+                info!("No YouTube API key configured, reading the public playlist page instead...");
+                fetch_videos_from_page(client, playlist_id).await.map_err(|e| format!("Error while reading the playlist page: {}", e))?
+            };
+
+            Ok(videos.iter().filter_map(parse_video).collect())
+        })
+    }
+}
+
+/// Artist and title come from "Artist - Title" video titles, or from the channel name for
+/// auto-generated "Artist - Topic" channels.
+fn parse_video(video: &Value) -> Option<SourceTrack> {
+
+    let raw_title = video["snippet"]["title"].as_str().unwrap_or("").to_string();
+
+    // deleted and private videos have no publish date
+    let Some(year) = video["contentDetails"]["videoPublishedAt"].as_str().and_then(|d| d.split('-').next()).and_then(|y| y.parse::<i32>().ok()) else {
+        warn!("Skipping unavailable video {}", raw_title);
+        return None;
+    };
+
+    let upload_channel = video["snippet"]["videoOwnerChannelTitle"].as_str().unwrap_or("");
+
+    let (artist, title) = match raw_title.split_once(" - ") {
+        Some((artist, title)) => (clean_artist(artist), clean_title(title.split(" - ").next().unwrap_or(title))),
+        None => (clean_artist(&upload_channel.replace(" - Topic", "")), clean_title(&raw_title)),
+    };
+
+    Some(SourceTrack {
+        id: video["contentDetails"]["videoId"].as_str().unwrap_or("").to_string(),
+        artist,
+        title,
+        raw_title,
+        year,
+        duration: video["contentDetails"]["duration"].as_str().and_then(parse_duration),
+        isrc: None,
+        release_year: None,
+        year_justification: None,
+    })
+}
+
+/// Exported playlists are JSON files, everything else is a playlist id.
+pub fn is_export_file(playlist_id: &str) -> bool {
+    playlist_id.to_lowercase().ends_with(".json")
+}
+
+pub fn parse_playlist_id(input: &str) -> Option<&str> {
+    if is_export_file(input.trim()) {
+        return Some(input.trim());
+    }
+    match input.starts_with("http") {
+        true => match input.rsplit_once("list=") {
+            Some((_, id)) => match id.split_once("&") {
+                Some((id, _)) => Some(id),
+                None => Some(id)
+            },
+            None => None
+        },
+        false => Some(input)
+    }
+}
+
+pub async fn fetch_videos(client: &Client, api_key: &str, playlist_id: &str) -> Result<Vec<Value>, Box<dyn Error>> {
+    let mut videos = Vec::new();
+    let mut page_token = String::new();
+
+    loop {
+        
+        let url = format!(
+            "https://youtube.googleapis.com/youtube/v3/playlistItems?part=snippet&part=contentDetails&maxResults=50&playlistId={}&pageToken={}&key={}",
+            playlist_id, page_token, api_key
+        );
+
+        let json = receive_json(client, &url).await?;
+
+        if let Some(items) = json["items"].as_array() {
+            videos.extend(items.clone());
+        }
+
+        if let Some(next_page_token) = json["nextPageToken"].as_str() {
+            page_token = next_page_token.to_string();
+        } else {
+            break;
+        }
+    }
+
+    Ok(videos)
+}
+
+/// Adds each video's ISO 8601 duration to its `contentDetails`, as the playlist items lack it.
+pub async fn fetch_durations(client: &Client, api_key: &str, videos: &mut [Value]) -> Result<(), Box<dyn Error>> {
+
+    for chunk in videos.chunks_mut(50) {
+
+        let ids: Vec<&str> = chunk.iter().filter_map(|v| v["contentDetails"]["videoId"].as_str()).collect();
+
+        let url = format!(
+            "https://youtube.googleapis.com/youtube/v3/videos?part=contentDetails&maxResults=50&id={}&key={}",
+            ids.join(","), api_key
+        );
+
+        let json = receive_json(client, &url).await?;
+
+        for item in json["items"].as_array().into_iter().flatten() {
+            let (Some(id), Some(duration)) = (item["id"].as_str(), item["contentDetails"]["duration"].as_str()) else {
+                continue;
+            };
+            if let Some(video) = chunk.iter_mut().find(|v| v["contentDetails"]["videoId"].as_str() == Some(id)) {
+                video["contentDetails"]["duration"] = Value::String(duration.to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Seconds of an ISO 8601 duration like `PT3M25S` or `P1DT2H`
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let mut seconds = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+
+    for c in duration.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            _ => {
+                let value: u64 = number.parse().ok()?;
+                number.clear();
+                seconds += value * match (c, in_time) {
+                    ('W', false) => 7 * 86400,
+                    ('D', false) => 86400,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+
+    number.is_empty().then_some(seconds)
+}
+
+pub fn clean_artist(input: &str) -> String {
+    let bracket_re = Regex::new(r"\[.*?\]").unwrap();
+    let mut temp = bracket_re.replace_all(input, "").trim().to_string();
+    if let Some(index) = temp.find("-").or(temp.find("|")) {
+        temp = temp.split_off(index);
+    }
+    temp.trim().to_string()
+}
+
+pub fn clean_title(input: &str) -> String {
+    let bracket_re = Regex::new(r"\[.*?\]").unwrap();
+    let remix_re = Regex::new(r"\([^)]*\)").unwrap();
+    let other_re = Regex::new(r"\|.*").unwrap();
+    let temp = bracket_re.replace_all(input, "").to_string();
+    let temp = remix_re
+        .replace_all(&temp, |caps: &regex::Captures| {
+            let content = &caps[0].to_lowercase();
+            if content.contains("remix") || content.contains("edit") || content.contains("vip") {
+                caps[0].to_string()
+            } else {
+                "".to_string()
+            }
+        })
+        .to_string();
+    let temp = other_re.replace_all(&temp, "").to_string();
+    let temp = temp.replace("\"", "");
+    temp.trim().to_string()
+}
@@ -0,0 +1,105 @@
+//! Playlist videos from an exported JSON file, for importing without a YouTube API key.
+//!
+//! Accepted are saved `playlistItems` API responses and older Google Takeout playlist files, which
+//! contain the same items, and yt-dlp dumps (`yt-dlp -J <playlist>`). Flat yt-dlp dumps
+//! (`--flat-playlist`) lack upload dates, so their videos can't be imported.
+
+use std::{error::Error, path::Path};
+use chrono::DateTime;
+use log::*;
+use serde_json::{json, Value};
+
+/// Videos in the same shape as the items of the `playlistItems` API endpoint.
+pub fn read_exported_videos(path: &Path) -> Result<Vec<Value>, Box<dyn Error>> {
+
+    let json: Value = serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))?;
+
+    let items = match &json {
+        Value::Array(items) => items,
+        _ => json["items"].as_array().or(json["entries"].as_array()).ok_or(format!("{} contains no playlist items", path.display()))?,
+    };
+
+    let videos: Vec<Value> = items.iter().map(|item| match item.get("contentDetails") {
+        Some(_) => item.clone(),
+        None => from_yt_dlp(item),
+    }).collect();
+
+    let undated = videos.iter().filter(|v| v["contentDetails"]["videoPublishedAt"].is_null()).count();
+    if undated > 0 {
+        warn!("{} of {} exported videos have no upload date. Exports made with --flat-playlist lack them.", undated, videos.len());
+    }
+
+    Ok(videos)
+}
+
+fn from_yt_dlp(entry: &Value) -> Value {
+
+    // upload_date is "20091025", the timestamps are seconds since 1970
+    let published_at = entry["upload_date"].as_str()
+        .filter(|d| d.len() == 8)
+        .map(|d| format!("{}-{}-{}T00:00:00Z", &d[..4], &d[4..6], &d[6..]))
+        .or_else(|| entry["timestamp"].as_i64().or(entry["release_timestamp"].as_i64())
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .map(|date| date.format("%Y-%m-%dT%H:%M:%SZ").to_string()));
+
+    json!({
+        "snippet": {
+            "title": entry["title"],
+            "videoOwnerChannelTitle": entry["channel"].as_str().or(entry["uploader"].as_str()),
+        },
+        "contentDetails": {
+            "videoId": entry["id"],
+            "videoPublishedAt": published_at,
+            "duration": entry["duration"].as_f64().map(|d| format!("PT{}S", d.round() as u64)),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_yt_dlp_entries() {
+        let video = from_yt_dlp(&json!({
+            "id": "fJ9rUzIMcZQ", "title": "Queen – Bohemian Rhapsody", "channel": "Queen Official",
+            "upload_date": "20081101", "duration": 354.6,
+        }));
+        assert_eq!(video["snippet"]["title"], "Queen – Bohemian Rhapsody");
+        assert_eq!(video["snippet"]["videoOwnerChannelTitle"], "Queen Official");
+        assert_eq!(video["contentDetails"]["videoId"], "fJ9rUzIMcZQ");
+        assert_eq!(video["contentDetails"]["videoPublishedAt"], "2008-11-01T00:00:00Z");
+        assert_eq!(video["contentDetails"]["duration"], "PT355S");
+
+        let video = from_yt_dlp(&json!({ "id": "a", "title": "A", "uploader": "Someone", "timestamp": 1225497600 }));
+        assert_eq!(video["snippet"]["videoOwnerChannelTitle"], "Someone");
+        assert_eq!(video["contentDetails"]["videoPublishedAt"], "2008-11-01T00:00:00Z");
+        assert!(video["contentDetails"]["duration"].is_null());
+
+        // flat playlists have neither
+        let video = from_yt_dlp(&json!({ "id": "b", "title": "B", "upload_date": null }));
+        assert!(video["contentDetails"]["videoPublishedAt"].is_null());
+    }
+
+    #[test]
+    fn reads_api_responses_and_yt_dlp_dumps() {
+        let path = std::env::temp_dir().join(format!("carnister-export-{}.json", std::process::id()));
+        let read = |content: &Value| {
+            std::fs::write(&path, content.to_string()).unwrap();
+            let videos = read_exported_videos(&path);
+            std::fs::remove_file(&path).unwrap();
+            videos
+        };
+
+        let item = json!({ "snippet": { "title": "A" }, "contentDetails": { "videoId": "a", "videoPublishedAt": "2009-10-25T06:57:33Z" } });
+        let videos = read(&json!({ "kind": "youtube#playlistItemListResponse", "items": [item] })).unwrap();
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0], item);
+        assert_eq!(read(&json!([item.clone()])).unwrap(), [item]);
+
+        let videos = read(&json!({ "_type": "playlist", "entries": [{ "id": "b", "title": "B", "upload_date": "20091025" }] })).unwrap();
+        assert_eq!(videos[0]["contentDetails"]["videoId"], "b");
+
+        assert!(read(&json!({ "title": "Not a playlist" })).is_err());
+    }
+}
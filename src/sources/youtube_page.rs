@@ -0,0 +1,196 @@
+//! Playlist videos from the public playlist page, for importing without a YouTube API key.
+//!
+//! The page embeds its first 100 videos as `ytInitialData`, further videos are requested with the
+//! continuation tokens it contains, like the website does when scrolling. The page only states
+//! upload dates relative to today ("3 years ago"), so the upload years are approximate.
+
+use std::{collections::HashSet, error::Error};
+use chrono::{Months, TimeDelta, Utc};
+use log::*;
+use regex::Regex;
+use reqwest::{header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, COOKIE, USER_AGENT}, Client, Url};
+use serde_json::{json, Value};
+
+use crate::http::{post_json, receive_text};
+
+/// YouTube serves a reduced page to unknown clients
+const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+/// Skips the cookie consent page shown in the EU
+const CONSENT_COOKIE: &str = "SOCS=CAI";
+
+/// Videos in the same shape as the items of the `playlistItems` API endpoint, with the fields
+/// [`super::youtube`] reads: title, channel, video id, upload date and duration.
+pub async fn fetch_videos_from_page(client: &Client, playlist_id: &str) -> Result<Vec<Value>, Box<dyn Error>> {
+
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static(BROWSER_USER_AGENT));
+    headers.insert(COOKIE, HeaderValue::from_static(CONSENT_COOKIE));
+    headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en"));
+
+    let url = Url::parse_with_params("https://www.youtube.com/playlist", [("list", playlist_id), ("hl", "en")])?;
+    let page = receive_text(client, url.as_str(), headers.clone()).await?;
+
+    let initial_data = embedded_json(&page, "ytInitialData").ok_or("The playlist page contains no playlist data. Is the playlist public?")?;
+    let client_version = Regex::new(r#""INNERTUBE_CLIENT_VERSION":"([^"]+)""#).unwrap()
+        .captures(&page)
+        .map(|c| c[1].to_string())
+        .unwrap_or_else(|| "2.20240101.00.00".to_string());
+
+    let mut renderers = Vec::new();
+    let mut continuation = collect_renderers(&initial_data, &mut renderers);
+    let mut requested = HashSet::new();
+
+    while let Some(token) = continuation {
+        // guards against pages that lead back to themselves
+        if token.is_empty() || !requested.insert(token.clone()) {
+            warn!("The playlist page repeated a continuation, stopping after {} videos", renderers.len());
+            break;
+        }
+        let found = renderers.len();
+        let json = post_json(client, "https://www.youtube.com/youtubei/v1/browse?prettyPrint=false", headers.clone(), &json!({
+            "context": { "client": { "clientName": "WEB", "clientVersion": client_version, "hl": "en" } },
+            "continuation": token,
+        })).await?;
+        continuation = collect_renderers(&json, &mut renderers);
+        if renderers.len() == found {
+            warn!("The playlist page sent no further videos, stopping after {} videos", renderers.len());
+            break;
+        }
+    }
+
+    info!("Read {} videos from the playlist page, upload years are estimated from texts like \"3 years ago\"", renderers.len());
+
+    Ok(renderers.iter().map(to_playlist_item).collect())
+}
+
+/// The JSON object assigned to `variable` in a script of the page.
+fn embedded_json(page: &str, variable: &str) -> Option<Value> {
+    let start = page.find(&format!("{} = ", variable))? + variable.len() + 3;
+    // the object is followed by the rest of the script, so only read the first value
+    serde_json::Deserializer::from_str(&page[start..]).into_iter::<Value>().next()?.ok()
+}
+
+/// Appends all `playlistVideoRenderer`s in document order and returns the continuation token for
+/// the next videos, if there are more.
+fn collect_renderers(json: &Value, renderers: &mut Vec<Value>) -> Option<String> {
+    let mut continuation = None;
+    match json {
+        Value::Object(object) => {
+            if let Some(renderer) = object.get("playlistVideoRenderer") {
+                renderers.push(renderer.clone());
+                return None;
+            }
+            if let Some(item) = object.get("continuationItemRenderer") {
+                return item["continuationEndpoint"]["continuationCommand"]["token"].as_str().map(str::to_string);
+            }
+            for value in object.values() {
+                continuation = collect_renderers(value, renderers).or(continuation);
+            }
+        },
+        Value::Array(array) => {
+            for value in array {
+                continuation = collect_renderers(value, renderers).or(continuation);
+            }
+        },
+        _ => (),
+    }
+    continuation
+}
+
+fn to_playlist_item(renderer: &Value) -> Value {
+
+    let text = |value: &Value| value["runs"][0]["text"].as_str().or(value["simpleText"].as_str()).unwrap_or("").to_string();
+
+    // unavailable videos have no upload date and are skipped like those from the API
+    let published_at = renderer["videoInfo"]["runs"].as_array().into_iter().flatten()
+        .filter_map(|run| run["text"].as_str())
+        .find_map(estimate_upload_date);
+
+    json!({
+        "snippet": {
+            "title": text(&renderer["title"]),
+            "videoOwnerChannelTitle": text(&renderer["shortBylineText"]),
+        },
+        "contentDetails": {
+            "videoId": renderer["videoId"],
+            "videoPublishedAt": published_at,
+            "duration": renderer["lengthSeconds"].as_str().map(|s| format!("PT{}S", s)),
+        },
+    })
+}
+
+/// RFC 3339 date for texts like "3 years ago" or "Streamed 2 weeks ago".
+fn estimate_upload_date(text: &str) -> Option<String> {
+
+    let captures = Regex::new(r"(\d+) (second|minute|hour|day|week|month|year)s? ago").unwrap().captures(text)?;
+    let amount: u32 = captures[1].parse().ok()?;
+    let now = Utc::now();
+
+    let date = match &captures[2] {
+        "year" => now.checked_sub_months(Months::new(amount * 12))?,
+        "month" => now.checked_sub_months(Months::new(amount))?,
+        "week" => now - TimeDelta::weeks(amount as i64),
+        "day" => now - TimeDelta::days(amount as i64),
+        _ => now,
+    };
+
+    Some(date.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+    use super::*;
+
+    fn renderer(video_id: &str, title: &str, uploaded: &str) -> Value {
+        json!({ "playlistVideoRenderer": {
+            "videoId": video_id,
+            "title": { "runs": [{ "text": title }] },
+            "shortBylineText": { "runs": [{ "text": "Queen Official" }] },
+            "lengthSeconds": "355",
+            "videoInfo": { "runs": [{ "text": "1.9B views" }, { "text": " • " }, { "text": uploaded }] },
+        } })
+    }
+
+    #[test]
+    fn reads_the_embedded_data() {
+        let page = r#"<script>var ytInitialData = {"contents":{"list":[1,2]}};var ytInitialPlayerResponse = null;</script>"#;
+        assert_eq!(embedded_json(page, "ytInitialData"), Some(json!({ "contents": { "list": [1, 2] } })));
+        assert_eq!(embedded_json(page, "ytcfg"), None);
+        assert_eq!(embedded_json("var ytInitialData = {\"contents\":", "ytInitialData"), None);
+    }
+
+    #[test]
+    fn collects_videos_and_the_continuation() {
+        let page = json!({ "contents": { "tabs": [{ "sectionList": { "contents": [
+            renderer("fJ9rUzIMcZQ", "Queen – Bohemian Rhapsody (Official Video Remastered)", "15 years ago"),
+            renderer("HgzGwKwLmgM", "Queen - Don't Stop Me Now (Official Video)", "Streamed 2 weeks ago"),
+            { "continuationItemRenderer": { "continuationEndpoint": { "continuationCommand": { "token": "next-page" } } } },
+        ] } }] } });
+
+        let mut renderers = Vec::new();
+        assert_eq!(collect_renderers(&page, &mut renderers).as_deref(), Some("next-page"));
+        assert_eq!(collect_renderers(&json!({ "contents": [] }), &mut renderers), None);
+
+        let items: Vec<Value> = renderers.iter().map(to_playlist_item).collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["snippet"]["title"], "Queen – Bohemian Rhapsody (Official Video Remastered)");
+        assert_eq!(items[0]["snippet"]["videoOwnerChannelTitle"], "Queen Official");
+        assert_eq!(items[0]["contentDetails"]["videoId"], "fJ9rUzIMcZQ");
+        assert_eq!(items[0]["contentDetails"]["duration"], "PT355S");
+        let year = Utc::now().year() - 15;
+        assert!(items[0]["contentDetails"]["videoPublishedAt"].as_str().unwrap().starts_with(&year.to_string()));
+        assert_eq!(items[1]["contentDetails"]["videoId"], "HgzGwKwLmgM");
+    }
+
+    #[test]
+    fn estimates_upload_dates() {
+        let year = |text: &str| estimate_upload_date(text).map(|date| date[..4].parse::<i32>().unwrap());
+        assert_eq!(year("3 years ago"), Some(Utc::now().year() - 3));
+        assert_eq!(year("1 year ago"), Some(Utc::now().year() - 1));
+        assert_eq!(year("5 hours ago"), Some(Utc::now().year()));
+        assert!(estimate_upload_date("Streamed 2 weeks ago").is_some());
+        assert_eq!(estimate_upload_date("1.9B views"), None);
+        assert_eq!(estimate_upload_date("Premiered Oct 25, 2009"), None);
+    }
+}